@@ -0,0 +1,109 @@
+use crate::{FileSystem, FsError, FsEventKind, Permissions};
+
+/// Grants one principal its own permissions on a node, in addition to the
+/// owner, group and other permission triples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AclEntry {
+    pub principal: String,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl AclEntry {
+    pub fn new(principal: &str, read: bool, write: bool, execute: bool) -> AclEntry {
+        AclEntry {
+            principal: principal.to_string(),
+            read,
+            write,
+            execute,
+        }
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::new(self.read, self.write, self.execute)
+    }
+}
+
+/// The kind of access checked by [`FileSystem::access_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl Permissions {
+    pub(crate) fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        }
+    }
+}
+
+impl FileSystem {
+    /// Adds `entry` to the node's ACL, replacing any earlier entry for the
+    /// same principal.
+    pub fn add_acl(&mut self, path: &str, entry: AclEntry) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.acl.retain(|e| e.principal != entry.principal);
+        metadata.acl.push(entry);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn remove_acl(&mut self, path: &str, principal: &str) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.acl.retain(|e| e.principal != principal);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    /// Returns the node's ACL entries in the order they were added.
+    pub fn acl(&self, path: &str) -> Result<Vec<AclEntry>, FsError> {
+        Ok(self.metadata_at(path)?.acl.clone())
+    }
+
+    /// Tells whether `user` may perform `access` on the node at `path`,
+    /// following symbolic links. An ACL entry naming `user` decides on its
+    /// own; otherwise the owner permissions apply to the owner and the other
+    /// permissions to everyone else. Group membership is not modelled, so
+    /// the group permissions are never consulted.
+    pub fn access_as(&self, user: &str, path: &str, access: Access) -> Result<bool, FsError> {
+        let metadata = self.metadata_at(&self.physical_path(path)?)?;
+        let permissions = match metadata.acl.iter().find(|e| e.principal == user) {
+            Some(entry) => entry.permissions(),
+            None if metadata.owner == user => metadata.permissions,
+            None => metadata.other_permissions,
+        };
+        Ok(permissions.allows(access))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Access, AclEntry, FileSystem};
+
+    #[test]
+    fn acl_grants_second_user_write() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, false).unwrap();
+        fs.set_owner("/a", "alice").unwrap();
+        fs.chmod("/a", "rw-r--r--").unwrap();
+        assert!(fs.access_as("alice", "/a", Access::Write).unwrap());
+        assert!(!fs.access_as("bob", "/a", Access::Write).unwrap());
+        assert!(fs.access_as("bob", "/a", Access::Read).unwrap());
+
+        fs.add_acl("/a", AclEntry::new("bob", false, true, false))
+            .unwrap();
+        assert!(fs.access_as("bob", "/a", Access::Write).unwrap());
+        assert!(!fs.access_as("bob", "/a", Access::Read).unwrap());
+        assert!(!fs.access_as("carol", "/a", Access::Write).unwrap());
+
+        fs.remove_acl("/a", "bob").unwrap();
+        assert!(!fs.access_as("bob", "/a", Access::Write).unwrap());
+    }
+}
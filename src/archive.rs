@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::path::{Component, Path};
+use std::time::{Duration, UNIX_EPOCH};
+
+use tar::{Archive, Builder, EntryType, Header};
+
+use crate::{
+    normalize, parent, Directory, FSNode, FileSystem, FsError, Metadata, Permissions,
+    MAX_SYMLINK_HOPS,
+};
+
+impl FileSystem {
+    /// Packs the subtree at `start` into a tar archive, with paths relative
+    /// to `start`. Entries carry each node's size, permission mode, mtime,
+    /// owner and group. Directories also get execute permission wherever they
+    /// have read permission, so the extracted tree stays traversable.
+    /// Symbolic links are stored as links to their unresolved targets.
+    pub fn to_tar(&self, start: &str) -> Result<Vec<u8>, FsError> {
+        let dir = self.navigate(&normalize(start)?)?;
+        let mut builder = Builder::new(Vec::new());
+        append_directory(&mut builder, dir, "").map_err(to_fs_error)?;
+        builder.into_inner().map_err(to_fs_error)
+    }
+
+    /// Unpacks a tar archive into the directory `virtual_dest`, creating it
+    /// and any missing parents. Files, directories, symbolic links and hard
+    /// links are unpacked with their permission mode and mtime; other entry
+    /// types are skipped. Entries with absolute paths or `..` components
+    /// are rejected with `InvalidPath`, and so are entries that would land
+    /// outside `virtual_dest` through a symbolic link and symbolic links
+    /// that lead outside it. Nothing is unpacked unless the whole archive
+    /// is.
+    pub fn from_tar(&mut self, data: &[u8], virtual_dest: &str) -> Result<(), FsError> {
+        let dest = normalize(virtual_dest)?
+            .iter()
+            .map(|p| format!("/{}", p))
+            .collect::<String>();
+        self.transaction(|fs| {
+            fs.create_dir_all(&dest)?;
+            let root = resolve_lenient(fs, &dest)?;
+            let mut symlinks = Vec::new();
+            let mut archive = Archive::new(data);
+            for entry in archive.entries().map_err(to_fs_error)? {
+                let mut entry = entry.map_err(to_fs_error)?;
+                let path = format!(
+                    "{}{}",
+                    dest,
+                    archive_path(&entry.path().map_err(to_fs_error)?)?
+                );
+                if path == dest {
+                    continue;
+                }
+                let link_target = || -> Result<String, FsError> {
+                    let target = entry.link_name().map_err(to_fs_error)?;
+                    let target = target.ok_or(FsError::InvalidPath)?;
+                    target
+                        .to_str()
+                        .map(str::to_string)
+                        .ok_or(FsError::InvalidUtf8)
+                };
+                let entry_type = entry.header().entry_type();
+                if !matches!(
+                    entry_type,
+                    EntryType::Regular
+                        | EntryType::Directory
+                        | EntryType::Symlink
+                        | EntryType::Link
+                ) {
+                    continue;
+                }
+                if entry_type == EntryType::Directory {
+                    check_within(fs, &root, &path)?;
+                } else {
+                    let parent = parent(&path).ok_or(FsError::InvalidPath)?;
+                    check_within(fs, &root, &parent)?;
+                    fs.create_dir_all(&parent)?;
+                }
+                match entry_type {
+                    EntryType::Directory => fs.create_dir_all(&path)?,
+                    EntryType::Symlink => {
+                        fs.create_symlink(&path, &link_target()?)?;
+                        symlinks.push(path.clone());
+                    }
+                    EntryType::Link => {
+                        let target = archive_path(Path::new(&link_target()?))?;
+                        let existing = format!("{}{}", dest, target);
+                        check_within(fs, &root, &existing)?;
+                        fs.create_hard_link(&existing, &path)?;
+                    }
+                    _ => {
+                        let mut content = Vec::new();
+                        entry.read_to_end(&mut content).map_err(to_fs_error)?;
+                        fs.create(&path, Some(content), false)?;
+                    }
+                }
+                let header = entry.header();
+                let metadata = fs.metadata_at_mut(&path)?;
+                if let Ok(mode) = header.mode() {
+                    metadata.permissions = Permissions::from_bits(mode >> 6);
+                    metadata.group_permissions = Permissions::from_bits(mode >> 3);
+                    metadata.other_permissions = Permissions::from_bits(mode);
+                }
+                if let Ok(mtime) = header.mtime() {
+                    metadata.modified_at = UNIX_EPOCH + Duration::from_secs(mtime);
+                }
+                fs.sync_links(&path);
+            }
+            // A link may only lead outside once a later entry completes the
+            // way there, so links are checked again at the end.
+            for link in &symlinks {
+                check_within(fs, &root, link)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Fails with `InvalidPath` unless `path`, with every symbolic link along
+/// it followed, lies within the resolved directory `root`.
+fn check_within(fs: &FileSystem, root: &[String], path: &str) -> Result<(), FsError> {
+    if resolve_lenient(fs, path)?.starts_with(root) {
+        Ok(())
+    } else {
+        Err(FsError::InvalidPath)
+    }
+}
+
+/// Follows the symbolic links along `path` like `resolve` with the last
+/// component followed too, except that components which don't exist are
+/// kept as they are instead of failing. This is where the path leads once
+/// the missing parts get created.
+fn resolve_lenient(fs: &FileSystem, path: &str) -> Result<Vec<String>, FsError> {
+    let mut resolved: Vec<String> = Vec::new();
+    let mut pending: VecDeque<String> = normalize(path)?.into();
+    let mut hops = 0;
+    while let Some(part) = pending.pop_front() {
+        match part.as_str() {
+            "" | "." => continue,
+            ".." => {
+                resolved.pop().ok_or(FsError::InvalidPath)?;
+                continue;
+            }
+            _ => {}
+        }
+        let dir = fs.navigate_physical(&resolved).ok();
+        let part = dir.map_or(part.clone(), |dir| {
+            dir.entry_name(&part, fs.case_insensitive)
+        });
+        match dir.and_then(|dir| dir.nodes.get(&part)) {
+            Some(FSNode::Symlink(link)) => {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS {
+                    return Err(FsError::TooManySymlinks);
+                }
+                if link.target.starts_with('/') {
+                    resolved.clear();
+                }
+                for component in link.target.split('/').rev() {
+                    pending.push_front(component.to_string());
+                }
+            }
+            _ => resolved.push(part),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Turns a path inside an archive into `/`-prefixed components, refusing
+/// anything that could land outside the destination.
+fn archive_path(path: &Path) -> Result<String, FsError> {
+    let mut joined = String::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => {
+                joined.push('/');
+                joined.push_str(part.to_str().ok_or(FsError::InvalidUtf8)?);
+            }
+            Component::CurDir => {}
+            _ => return Err(FsError::InvalidPath),
+        }
+    }
+    Ok(joined)
+}
+
+fn append_directory(
+    builder: &mut Builder<Vec<u8>>,
+    dir: &Directory,
+    prefix: &str,
+) -> io::Result<()> {
+    for (name, node) in &dir.nodes {
+        let path = format!("{}{}", prefix, name);
+        match node {
+            FSNode::File(file) => {
+                let content = file.content();
+                let mut header = header_for(&file.metadata, EntryType::Regular, content.len())?;
+                builder.append_data(&mut header, &path, content.as_slice())?;
+            }
+            FSNode::Directory(subdir) => {
+                let mut header = header_for(&subdir.metadata, EntryType::Directory, 0)?;
+                builder.append_data(&mut header, format!("{}/", path), io::empty())?;
+                append_directory(builder, subdir, &format!("{}/", path))?;
+            }
+            FSNode::Symlink(link) => {
+                let mut header = header_for(&link.metadata, EntryType::Symlink, 0)?;
+                builder.append_link(&mut header, &path, &link.target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn header_for(metadata: &Metadata, entry_type: EntryType, size: usize) -> io::Result<Header> {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_size(size as u64);
+    let mut mode = metadata.permissions.bits() << 6
+        | metadata.group_permissions.bits() << 3
+        | metadata.other_permissions.bits();
+    if entry_type == EntryType::Directory {
+        mode |= (mode & 0o444) >> 2;
+    }
+    header.set_mode(mode);
+    let mtime = metadata
+        .modified_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    header.set_mtime(mtime);
+    header.set_username(&metadata.owner)?;
+    header.set_groupname(&metadata.group)?;
+    Ok(header)
+}
+
+fn to_fs_error(err: io::Error) -> FsError {
+    FsError::Serialization(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use tar::{Archive, Builder, EntryType, Header};
+
+    use crate::{FileSystem, FsError};
+
+    /// Builds an archive from `(path, type, link target or content)`
+    /// entries, without the path checks `tar::Builder` applies.
+    fn archive(entries: &[(&str, EntryType, &str)]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, entry_type, data) in entries {
+            let mut header = Header::new_gnu();
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            let name = &mut header.as_old_mut().name;
+            name[..path.len()].copy_from_slice(path.as_bytes());
+            let content = match entry_type {
+                EntryType::Symlink | EntryType::Link => {
+                    header.set_link_name(data).unwrap();
+                    ""
+                }
+                _ => data,
+            };
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append(&header, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn to_tar_reads_back_with_tar_crate() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/root/sub").unwrap();
+        fs.create("/root/a.txt", Some(b"alpha".to_vec()), false)
+            .unwrap();
+        fs.create("/root/sub/b.txt", Some(b"beta!".to_vec()), false)
+            .unwrap();
+        let data = fs.to_tar("/root").unwrap();
+
+        let mut entries = Vec::new();
+        for entry in Archive::new(data.as_slice()).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let entry_type = entry.header().entry_type();
+            let size = entry.header().size().unwrap();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            entries.push((path, entry_type, size, content));
+        }
+        assert_eq!(
+            entries,
+            [
+                (
+                    "a.txt".to_string(),
+                    EntryType::Regular,
+                    5,
+                    b"alpha".to_vec()
+                ),
+                ("sub/".to_string(), EntryType::Directory, 0, Vec::new()),
+                (
+                    "sub/b.txt".to_string(),
+                    EntryType::Regular,
+                    5,
+                    b"beta!".to_vec()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_tar_unpacks_tree() {
+        let data = archive(&[
+            ("docs/", EntryType::Directory, ""),
+            ("docs/a.txt", EntryType::Regular, "alpha"),
+            ("src/lib/b.rs", EntryType::Regular, "beta"),
+            ("docs/link", EntryType::Symlink, "a.txt"),
+            ("docs/hard", EntryType::Link, "docs/a.txt"),
+        ]);
+        let mut fs = FileSystem::new();
+        fs.from_tar(&data, "/dest").unwrap();
+        assert_eq!(fs.read_file("/dest/docs/a.txt").unwrap(), b"alpha");
+        assert_eq!(fs.read_file("/dest/src/lib/b.rs").unwrap(), b"beta");
+        assert_eq!(fs.read_file("/dest/docs/link").unwrap(), b"alpha");
+        assert_eq!(fs.link_count("/dest/docs/hard").unwrap(), 2);
+        assert_eq!(fs.metadata("/dest/docs/a.txt").unwrap().size, 5);
+    }
+
+    #[test]
+    fn from_tar_rejects_traversal() {
+        let data = archive(&[("../escape.txt", EntryType::Regular, "x")]);
+        let mut fs = FileSystem::new();
+        assert_eq!(fs.from_tar(&data, "/dest"), Err(FsError::InvalidPath));
+        assert!(!fs.exists("/escape.txt"));
+    }
+
+    #[test]
+    fn from_tar_rejects_writes_through_escaping_links() {
+        let mut fs = FileSystem::new();
+        let data = archive(&[
+            ("evil", EntryType::Symlink, "/"),
+            ("evil/pwned.txt", EntryType::Regular, "x"),
+        ]);
+        assert_eq!(fs.from_tar(&data, "/dest"), Err(FsError::InvalidPath));
+        assert!(!fs.exists("/pwned.txt"));
+        assert!(!fs.exists("/dest"));
+
+        let data = archive(&[("up", EntryType::Symlink, "..")]);
+        assert_eq!(fs.from_tar(&data, "/dest"), Err(FsError::InvalidPath));
+
+        // Neither link escapes until both exist.
+        let data = archive(&[
+            ("d/", EntryType::Directory, ""),
+            ("d/l2", EntryType::Symlink, "l/../x"),
+            ("d/l", EntryType::Symlink, ".."),
+        ]);
+        assert_eq!(fs.from_tar(&data, "/dest"), Err(FsError::InvalidPath));
+        assert!(fs.list_directory("/").unwrap().is_empty());
+    }
+}
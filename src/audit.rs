@@ -0,0 +1,126 @@
+use std::time::SystemTime;
+
+use crate::{FileSystem, FsEventKind};
+
+/// One recorded change, as kept by the audit log. `kind` and `path` are
+/// the same as in the matching [`crate::FsEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub timestamp: SystemTime,
+    pub kind: FsEventKind,
+    pub path: String,
+}
+
+impl FileSystem {
+    /// Turns the audit log on or off. While on, every change that watchers
+    /// would be told about is also appended to the log, timestamped by the
+    /// filesystem's clock. Turning it off discards the entries.
+    pub fn set_audit_log(&mut self, enabled: bool) {
+        match (enabled, &self.audit_log) {
+            (true, None) => self.audit_log = Some(Vec::new()),
+            (false, _) => self.audit_log = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    /// The recorded changes, oldest first. Empty while the log is off.
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        self.audit_log.as_deref().unwrap_or_default()
+    }
+
+    pub fn clear_audit_log(&mut self) {
+        if let Some(log) = &mut self.audit_log {
+            log.clear();
+        }
+    }
+
+    pub(crate) fn record(&mut self, kind: &FsEventKind, path: &str) {
+        let timestamp = self.now();
+        if let Some(log) = &mut self.audit_log {
+            log.push(AuditEntry {
+                timestamp,
+                kind: kind.clone(),
+                path: path.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::tests::FakeClock;
+    use crate::{AuditEntry, FileSystem, FsEventKind};
+
+    #[test]
+    fn audit_log_records_changes_in_order() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/quiet", None, false).unwrap();
+        fs.set_audit_log(true);
+        fs.create("/a", None, false).unwrap();
+        clock.advance(1);
+        fs.write_file("/a", b"x".to_vec(), false).unwrap();
+        clock.advance(1);
+        fs.touch("/a").unwrap();
+        clock.advance(1);
+        fs.rename("/a", "b").unwrap();
+        clock.advance(1);
+        fs.delete("/b").unwrap();
+        fs.read_file("/quiet").unwrap();
+
+        let entry = |secs, kind, path: &str| AuditEntry {
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            kind,
+            path: path.to_string(),
+        };
+        assert_eq!(
+            fs.audit_log(),
+            [
+                entry(0, FsEventKind::Create, "/a"),
+                entry(1, FsEventKind::Modify, "/a"),
+                entry(2, FsEventKind::Metadata, "/a"),
+                entry(
+                    3,
+                    FsEventKind::Rename {
+                        to: "/b".to_string(),
+                    },
+                    "/a",
+                ),
+                entry(4, FsEventKind::Delete, "/b"),
+            ]
+        );
+        fs.clear_audit_log();
+        assert!(fs.audit_log().is_empty());
+    }
+
+    #[test]
+    fn mount_and_session_changes_are_recorded() {
+        let mut sub = FileSystem::new();
+        sub.create("/x", None, false).unwrap();
+        let mut fs = FileSystem::new();
+        fs.create("/mnt", None, true).unwrap();
+        fs.chmod("/", "rwxrwxrwx").unwrap();
+        fs.set_audit_log(true);
+        fs.mount("/mnt", sub).unwrap();
+        fs.as_user("alice").create("/mine", None, false).unwrap();
+        fs.unmount("/mnt").unwrap();
+        let log = fs
+            .audit_log()
+            .iter()
+            .map(|entry| (entry.kind.clone(), entry.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            log,
+            [
+                (FsEventKind::Metadata, "/mnt"),
+                (FsEventKind::Create, "/mnt/x"),
+                (FsEventKind::Create, "/mine"),
+                (FsEventKind::Metadata, "/mine"),
+                (FsEventKind::Delete, "/mnt/x"),
+                (FsEventKind::Metadata, "/mnt"),
+            ]
+        );
+    }
+}
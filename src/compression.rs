@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{check_room, FileSystem, FsError};
+
+/// Turns file content into its stored form and back. `decompress` only
+/// ever receives output of the same compressor's `compress`.
+pub trait Compressor: Send + Sync {
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+    fn decompress(&self, data: &[u8]) -> Vec<u8>;
+}
+
+/// Stores content unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopCompressor;
+
+impl Compressor for NoopCompressor {
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        data.to_vec()
+    }
+
+    fn decompress(&self, data: &[u8]) -> Vec<u8> {
+        data.to_vec()
+    }
+}
+
+/// Run-length encoding as `(count, byte)` pairs. Cheap and effective on
+/// repetitive data, but doubles the size of data without runs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RleCompressor;
+
+impl Compressor for RleCompressor {
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &byte in data {
+            match out.len().checked_sub(2) {
+                Some(last) if out[last + 1] == byte && out[last] < u8::MAX => out[last] += 1,
+                _ => out.extend([1, byte]),
+            }
+        }
+        out
+    }
+
+    fn decompress(&self, data: &[u8]) -> Vec<u8> {
+        data.chunks_exact(2)
+            .flat_map(|pair| std::iter::repeat_n(pair[1], pair[0] as usize))
+            .collect()
+    }
+}
+
+/// The bytes behind a file, shared by all of its hard links. With a
+/// compressor attached, `data` holds the compressed form. `data` itself may
+/// also be shared with unrelated files holding identical bytes; writers go
+/// through `Arc::make_mut`, so such files diverge on their first change.
+#[derive(Clone)]
+pub(crate) struct Content {
+    pub(crate) data: Arc<Vec<u8>>,
+    pub(crate) compressor: Option<Arc<dyn Compressor>>,
+    /// Earlier uncompressed versions, oldest first, when history is on.
+    pub(crate) history: VecDeque<Vec<u8>>,
+}
+
+impl Content {
+    pub(crate) fn new(data: Vec<u8>) -> Content {
+        Content {
+            data: Arc::new(data),
+            compressor: None,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Content")
+            .field("stored_len", &self.data.len())
+            .field("compressed", &self.compressor.is_some())
+            .field("versions", &self.history.len())
+            .finish()
+    }
+}
+
+/// Read access to a file's uncompressed bytes.
+pub(crate) enum ContentRef<'a> {
+    Plain(RwLockReadGuard<'a, Content>),
+    Decompressed(Vec<u8>),
+}
+
+impl<'a> ContentRef<'a> {
+    pub(crate) fn new(guard: RwLockReadGuard<'a, Content>) -> ContentRef<'a> {
+        match &guard.compressor {
+            Some(compressor) => ContentRef::Decompressed(compressor.decompress(&guard.data)),
+            None => ContentRef::Plain(guard),
+        }
+    }
+}
+
+impl Deref for ContentRef<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        match self {
+            ContentRef::Plain(guard) => &guard.data,
+            ContentRef::Decompressed(data) => data,
+        }
+    }
+}
+
+/// Write access to a file's uncompressed bytes. Compressed content is
+/// decompressed up front and compressed again when the guard is dropped.
+pub(crate) struct ContentMut<'a> {
+    guard: RwLockWriteGuard<'a, Content>,
+    decompressed: Option<Vec<u8>>,
+}
+
+impl<'a> ContentMut<'a> {
+    pub(crate) fn new(guard: RwLockWriteGuard<'a, Content>) -> ContentMut<'a> {
+        let decompressed = guard
+            .compressor
+            .as_ref()
+            .map(|compressor| compressor.decompress(&guard.data));
+        ContentMut {
+            guard,
+            decompressed,
+        }
+    }
+}
+
+impl Deref for ContentMut<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        self.decompressed.as_ref().unwrap_or(&self.guard.data)
+    }
+}
+
+impl DerefMut for ContentMut<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        match &mut self.decompressed {
+            Some(data) => data,
+            None => Arc::make_mut(&mut self.guard.data),
+        }
+    }
+}
+
+impl Drop for ContentMut<'_> {
+    fn drop(&mut self) {
+        if let (Some(data), Some(compressor)) = (&self.decompressed, &self.guard.compressor) {
+            let compressed = compressor.compress(data);
+            self.guard.data = Arc::new(compressed);
+        }
+    }
+}
+
+impl FileSystem {
+    /// Sets the compressor used by later calls to
+    /// [`FileSystem::set_compression`]. Files that are already compressed
+    /// keep the compressor they were compressed with.
+    pub fn set_compressor(&mut self, compressor: Box<dyn Compressor>) {
+        self.compressor = Arc::from(compressor);
+    }
+
+    /// Switches compressed storage for the file at `path` on or off. Reads
+    /// and writes keep seeing the uncompressed bytes; only `used_bytes` and
+    /// quota accounting see the stored size. Quota checks on writes use the
+    /// uncompressed length, so they err on the side of refusing.
+    pub fn set_compression(&mut self, path: &str, enabled: bool) -> Result<(), FsError> {
+        self.check_thawed()?;
+        let room = self.room_for(path)?;
+        let compressor = Arc::clone(&self.compressor);
+        let file = self.get_file(path)?;
+        let mut content = file.raw_content_mut();
+        if content.compressor.is_some() == enabled {
+            return Ok(());
+        }
+        let stored = match &content.compressor {
+            Some(current) => current.decompress(&content.data),
+            None => compressor.compress(&content.data),
+        };
+        check_room(room, content.data.len(), stored.len())?;
+        content.data = Arc::new(stored);
+        content.compressor = enabled.then_some(compressor);
+        drop(content);
+        self.intern(path);
+        Ok(())
+    }
+
+    pub fn is_compressed(&self, path: &str) -> Result<bool, FsError> {
+        let file = self.get_file(path)?;
+        Ok(file.raw_content().compressor.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileSystem;
+
+    #[test]
+    fn compression_shrinks_repetitive_content() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(vec![b'x'; 1000]), false).unwrap();
+        assert_eq!(fs.used_bytes(), 1000);
+        fs.set_compression("/a", true).unwrap();
+        assert!(fs.is_compressed("/a").unwrap());
+        assert!(fs.used_bytes() < 100);
+        assert_eq!(fs.read_file("/a").unwrap(), vec![b'x'; 1000]);
+
+        fs.write_file("/a", b"yy".to_vec(), true).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap().len(), 1002);
+        fs.set_compression("/a", false).unwrap();
+        assert_eq!(fs.used_bytes(), 1002);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, PoisonError, Weak};
+
+use crate::{fnv1a, Directory, FSNode, FileSystem};
+
+/// How much content is shared between files holding identical bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Distinct stored buffers.
+    pub unique_blocks: usize,
+    /// Bytes that would be stored a second time without sharing.
+    pub saved_bytes: usize,
+}
+
+/// Content buffers keyed by the hash of their bytes. Entries are weak, so
+/// the store never keeps content alive on its own.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BlockStore {
+    blocks: HashMap<u64, Vec<Weak<Vec<u8>>>>,
+}
+
+impl BlockStore {
+    /// Returns the stored buffer equal to `data`, or records `data` as the
+    /// buffer to share from now on.
+    fn intern(&mut self, data: &Arc<Vec<u8>>) -> Arc<Vec<u8>> {
+        let bucket = self.blocks.entry(fnv1a(data)).or_default();
+        bucket.retain(|block| block.strong_count() > 0);
+        if let Some(block) = bucket
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|block| block == data)
+        {
+            return block;
+        }
+        bucket.push(Arc::downgrade(data));
+        Arc::clone(data)
+    }
+}
+
+impl FileSystem {
+    /// Makes the file at `path` share its stored bytes with any other file
+    /// whose content is identical. Called after every create and write.
+    pub(crate) fn intern(&mut self, path: &str) {
+        let Ok(file) = self.get_file(path) else {
+            return;
+        };
+        let content = Arc::clone(&file.content);
+        let mut content = content.write().unwrap_or_else(PoisonError::into_inner);
+        content.data = self.blocks.intern(&content.data);
+    }
+
+    pub fn dedup_stats(&self) -> DedupStats {
+        let mut files = HashSet::new();
+        let mut blocks = HashSet::new();
+        let mut stats = DedupStats::default();
+        collect_blocks(&self.root, &mut files, &mut blocks, &mut stats);
+        stats.unique_blocks = blocks.len();
+        stats
+    }
+}
+
+fn collect_blocks(
+    dir: &Directory,
+    files: &mut HashSet<usize>,
+    blocks: &mut HashSet<usize>,
+    stats: &mut DedupStats,
+) {
+    for node in dir.nodes.values() {
+        match node {
+            FSNode::File(file) if files.insert(Arc::as_ptr(&file.content) as usize) => {
+                let content = file.raw_content();
+                if !blocks.insert(Arc::as_ptr(&content.data) as usize) {
+                    stats.saved_bytes += content.data.len();
+                }
+            }
+            FSNode::Directory(subdir) => collect_blocks(subdir, files, blocks, stats),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DedupStats, FileSystem};
+
+    #[test]
+    fn identical_files_share_until_they_diverge() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(vec![7; 100]), false).unwrap();
+        fs.create("/b", Some(vec![7; 100]), false).unwrap();
+        assert_eq!(
+            fs.dedup_stats(),
+            DedupStats {
+                unique_blocks: 1,
+                saved_bytes: 100,
+            }
+        );
+        fs.write_file("/b", vec![1], true).unwrap();
+        assert_eq!(fs.dedup_stats().saved_bytes, 0);
+        assert_eq!(fs.read_file("/a").unwrap(), vec![7; 100]);
+        assert_eq!(fs.read_file("/b").unwrap().len(), 101);
+    }
+}
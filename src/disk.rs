@@ -0,0 +1,193 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{normalize, Directory, FSNode, FileSystem, FsError};
+
+impl FileSystem {
+    /// Writes the subtree at `virtual_root` into `dest`, creating `dest` if
+    /// needed. On Unix each file's permission mode is applied to the real
+    /// file; directories keep the platform defaults so the exported tree
+    /// always stays traversable. Symbolic links are skipped, since their
+    /// virtual targets mean nothing on the real filesystem.
+    pub fn export_to_disk(&self, virtual_root: &str, dest: &Path) -> io::Result<()> {
+        let parts = normalize(virtual_root).map_err(to_io_error)?;
+        let dir = self.navigate(&parts).map_err(to_io_error)?;
+        fs::create_dir_all(dest)?;
+        export_directory(dir, dest)
+    }
+
+    /// Reads the real directory `src` into the tree below `virtual_dest`,
+    /// creating `virtual_dest` if needed. Symbolic links are skipped rather
+    /// than followed, so an import can never loop or escape `src`.
+    pub fn import_from_disk(&mut self, src: &Path, virtual_dest: &str) -> io::Result<()> {
+        self.create_dir_all(virtual_dest).map_err(to_io_error)?;
+        let prefix = normalize(virtual_dest)
+            .map_err(to_io_error)?
+            .iter()
+            .map(|p| format!("/{}", p))
+            .collect::<String>();
+        self.import_directory(src, &prefix)
+    }
+
+    fn import_directory(&mut self, src: &Path, prefix: &str) -> io::Result<()> {
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                continue;
+            }
+            let name = entry.file_name().into_string().map_err(|name| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("non UTF-8 file name: {:?}", name),
+                )
+            })?;
+            let path = format!("{}/{}", prefix, name);
+            if file_type.is_dir() {
+                self.create_dir_all(&path).map_err(to_io_error)?;
+                self.import_directory(&entry.path(), &path)?;
+            } else {
+                let content = fs::read(entry.path())?;
+                self.create(&path, Some(content), false)
+                    .map_err(to_io_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn export_directory(dir: &Directory, dest: &Path) -> io::Result<()> {
+    for (name, node) in &dir.nodes {
+        let path = entry_path(dest, name)?;
+        match node {
+            FSNode::File(file) => {
+                fs::write(&path, &*file.content())?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let metadata = &file.metadata;
+                    let mode = metadata.permissions.bits() << 6
+                        | metadata.group_permissions.bits() << 3
+                        | metadata.other_permissions.bits();
+                    fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+                }
+            }
+            FSNode::Directory(subdir) => {
+                fs::create_dir_all(&path)?;
+                export_directory(subdir, &path)?;
+            }
+            FSNode::Symlink(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Joins `name` onto `dest`, refusing names that are not a single plain
+/// component, such as `..`, `a/b` or an absolute path, so an export can
+/// never write outside `dest`.
+fn entry_path(dest: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(part)), None) if part == OsStr::new(name) => Ok(dest.join(name)),
+        _ => Err(to_io_error(FsError::InvalidPath)),
+    }
+}
+
+pub(crate) fn to_io_error(err: FsError) -> io::Error {
+    let kind = match err {
+        FsError::NotFound => io::ErrorKind::NotFound,
+        FsError::AlreadyExists => io::ErrorKind::AlreadyExists,
+        FsError::PermissionDenied => io::ErrorKind::PermissionDenied,
+        FsError::InvalidPath | FsError::InvalidOffset => io::ErrorKind::InvalidInput,
+        FsError::InvalidUtf8 => io::ErrorKind::InvalidData,
+        FsError::QuotaExceeded => io::ErrorKind::StorageFull,
+        FsError::Cancelled => io::ErrorKind::Interrupted,
+        FsError::Busy => io::ErrorKind::ResourceBusy,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, process};
+
+    use crate::{FSNode, File, FileSystem, Metadata};
+
+    /// A fresh directory below the system temp directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = env::temp_dir().join(format!("filesystem-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn export_writes_tree_to_disk() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/out/sub").unwrap();
+        fs.create("/out/a.txt", Some(b"a".to_vec()), false).unwrap();
+        fs.create("/out/sub/b.txt", Some(b"b".to_vec()), false)
+            .unwrap();
+        let dir = TempDir::new("export");
+        let dest = dir.path().join("dest");
+        fs.export_to_disk("/out", &dest).unwrap();
+        assert_eq!(fs::read(dest.join("a.txt")).unwrap(), b"a");
+        assert_eq!(fs::read(dest.join("sub/b.txt")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn export_rejects_escaping_names() {
+        let dir = TempDir::new("export-escape");
+        let dest = dir.path().join("a/b/dest");
+        for name in ["../../escape.txt", "..", "x/y", "/abs"] {
+            let mut fs = FileSystem::new();
+            let metadata = Metadata::new(2, fs.now());
+            let file = File::new(name.to_string(), b"x".to_vec(), metadata);
+            fs.root.nodes.insert(name.to_string(), FSNode::File(file));
+            assert!(
+                fs.export_to_disk("/", &dest).is_err(),
+                "{} was exported",
+                name
+            );
+        }
+        assert!(!dir.path().join("a/escape.txt").exists());
+        assert!(!dir.path().join("a/b/dest/x").exists());
+    }
+
+    #[test]
+    fn import_reads_tree_from_disk() {
+        let dir = TempDir::new("import");
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), b"b").unwrap();
+        let mut fs = FileSystem::new();
+        fs.import_from_disk(dir.path(), "/in").unwrap();
+        assert_eq!(
+            fs.walk("/in").unwrap(),
+            [
+                ("/in/a.txt".to_string(), false),
+                ("/in/sub".to_string(), true),
+                ("/in/sub/b.txt".to_string(), false),
+            ]
+        );
+        assert_eq!(fs.read_file("/in/sub/b.txt").unwrap(), b"b");
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use crate::{to_io_error, FileSystem, FsError};
+
+/// A reader over a file's content as it was when the file was opened.
+/// Later writes to the file do not show up in an open reader.
+#[derive(Debug, Clone)]
+pub struct FileReader {
+    data: Arc<Vec<u8>>,
+    position: usize,
+}
+
+impl FileReader {
+    /// The number of bytes already read.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.data[self.position..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// A buffered writer into a file. Written bytes collect in memory and reach
+/// the file on [`Write::flush`] or when the writer is dropped; each flush
+/// appends the buffer, so writes land in order. A failed flush keeps the
+/// buffer for the next attempt. Errors from the flush on drop are ignored,
+/// so call `flush` to see them.
+pub struct FileWriter<'a> {
+    fs: &'a mut FileSystem,
+    path: String,
+    buffer: Vec<u8>,
+}
+
+impl Write for FileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.fs
+            .write_file(&self.path, self.buffer.clone(), true)
+            .map_err(to_io_error)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Drop for FileWriter<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl FileSystem {
+    /// Opens the file at `path` for reading through [`std::io::Read`]. The
+    /// reader shares the stored bytes instead of copying them, unless the
+    /// file is compressed. Like `read_range`, this does not touch
+    /// `accessed_at`.
+    pub fn open(&self, path: &str) -> Result<FileReader, FsError> {
+        let file = self.get_readable_file(path)?;
+        let content = file.raw_content();
+        let data = match &content.compressor {
+            Some(compressor) => Arc::new(compressor.decompress(&content.data)),
+            None => Arc::clone(&content.data),
+        };
+        Ok(FileReader { data, position: 0 })
+    }
+
+    /// Opens the file at `path` for writing through [`std::io::Write`].
+    /// Without `append` the file is emptied right away, like
+    /// [`std::fs::File::create`]; with it, writes go after the current
+    /// content.
+    pub fn open_writer(&mut self, path: &str, append: bool) -> Result<FileWriter<'_>, FsError> {
+        self.write_file(path, Vec::new(), append)?;
+        Ok(FileWriter {
+            fs: self,
+            path: path.to_string(),
+            buffer: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::FileSystem;
+
+    #[test]
+    fn read_to_end_reads_whole_file() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"hello world".to_vec()), false)
+            .unwrap();
+        let mut reader = fs.open("/a").unwrap();
+        let mut first = [0; 5];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"hello");
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b" world");
+        assert_eq!(reader.position(), 11);
+        assert_eq!(reader.read(&mut first).unwrap(), 0);
+    }
+
+    #[test]
+    fn writeln_lands_in_file_on_flush_and_drop() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"old".to_vec()), false).unwrap();
+        let mut writer = fs.open_writer("/a", false).unwrap();
+        writeln!(writer, "line {}", 1).unwrap();
+        writer.flush().unwrap();
+        writeln!(writer, "line {}", 2).unwrap();
+        drop(writer);
+        assert_eq!(fs.read_to_string("/a").unwrap(), "line 1\nline 2\n");
+
+        let mut writer = fs.open_writer("/a", true).unwrap();
+        writeln!(writer, "line 3").unwrap();
+        drop(writer);
+        assert_eq!(fs.read_lines("/a").unwrap(), ["line 1", "line 2", "line 3"]);
+    }
+}
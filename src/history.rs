@@ -0,0 +1,91 @@
+use crate::{check_room, File, FileSystem, FsError, FsEventKind};
+
+impl File {
+    /// Records `previous` as the newest earlier version, dropping the oldest
+    /// ones beyond `limit`.
+    pub(crate) fn push_version(&self, previous: Vec<u8>, limit: usize) {
+        let mut content = self.raw_content_mut();
+        content.history.push_back(previous);
+        while content.history.len() > limit {
+            content.history.pop_front();
+        }
+    }
+}
+
+impl FileSystem {
+    /// Keeps up to `limit` earlier versions of each file, recorded by
+    /// `write_file` and `update_file`. History is off by default; a limit of
+    /// 0 turns it off again. Hard links share one history, and a lower limit
+    /// trims a file's history on its next write.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history_limit
+    }
+
+    /// Returns the earlier versions of the file at `path`, newest first.
+    pub fn history(&self, path: &str) -> Result<Vec<Vec<u8>>, FsError> {
+        let file = self.get_readable_file(path)?;
+        let content = file.raw_content();
+        Ok(content.history.iter().rev().cloned().collect())
+    }
+
+    /// Restores the version `versions_back` writes ago, so 1 undoes the last
+    /// write. The versions newer than the restored one are discarded.
+    pub fn revert(&mut self, path: &str, versions_back: usize) -> Result<(), FsError> {
+        let now = self.now();
+        let room = self.room_for(path)?;
+        let file = self.get_file_mut(path)?;
+        if file.metadata.is_read_only {
+            return Err(FsError::ReadOnly);
+        }
+        if !file.metadata.permissions.write {
+            return Err(FsError::PermissionDenied);
+        }
+        if versions_back == 0 {
+            return Ok(());
+        }
+
+        let mut content = file.raw_content_mut();
+        let index = content
+            .history
+            .len()
+            .checked_sub(versions_back)
+            .ok_or(FsError::NotFound)?;
+        check_room(room, content.data.len(), content.history[index].len())?;
+        let restored = content.history.split_off(index).pop_front().unwrap();
+        drop(content);
+
+        let mut buffer = file.content_mut();
+        *buffer = restored;
+        let size = buffer.len();
+        drop(buffer);
+        file.metadata.size = size;
+        file.metadata.update_modified(now);
+        self.intern(path);
+        self.notify(FsEventKind::Modify, path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileSystem;
+
+    #[test]
+    fn revert_undoes_last_write() {
+        let mut fs = FileSystem::new();
+        fs.set_history_limit(2);
+        fs.create("/a", Some(b"v1".to_vec()), false).unwrap();
+        fs.write_file("/a", b"v2".to_vec(), false).unwrap();
+        fs.write_file("/a", b"v3".to_vec(), false).unwrap();
+        fs.write_file("/a", b"v4".to_vec(), false).unwrap();
+        assert_eq!(fs.history("/a").unwrap(), [b"v3".to_vec(), b"v2".to_vec()]);
+
+        fs.revert("/a", 1).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"v3");
+        assert_eq!(fs.history("/a").unwrap(), [b"v2".to_vec()]);
+    }
+}
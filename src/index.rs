@@ -0,0 +1,348 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::{Directory, FSNode, File, FileSystem, FsError, FsEventKind};
+
+/// Maps keys, such as tags or words, to the files they were drawn from.
+/// Paths are stored as resolved components, whose ordering matches the
+/// order of a walk. Kept up to date from the change events, so it sees
+/// exactly what watchers see.
+#[derive(Debug, Clone)]
+pub(crate) struct PathIndex {
+    keys_of: fn(&File) -> Vec<String>,
+    /// The event after which a file's keys have to be drawn again.
+    refresh_on: FsEventKind,
+    paths: HashMap<String, BTreeSet<Vec<String>>>,
+    keys: HashMap<Vec<String>, Vec<String>>,
+}
+
+impl PathIndex {
+    /// Indexes files by their tags.
+    pub(crate) fn tags() -> PathIndex {
+        PathIndex::new(|file| file.metadata.tags.clone(), FsEventKind::Metadata)
+    }
+
+    /// Indexes files by the whitespace-separated words of their content.
+    /// Content that is not valid UTF-8 yields no words.
+    pub(crate) fn words() -> PathIndex {
+        PathIndex::new(
+            |file| {
+                let content = file.content();
+                let Ok(text) = std::str::from_utf8(&content) else {
+                    return Vec::new();
+                };
+                let words = text.split_whitespace().collect::<BTreeSet<_>>();
+                words.into_iter().map(str::to_string).collect()
+            },
+            FsEventKind::Modify,
+        )
+    }
+
+    fn new(keys_of: fn(&File) -> Vec<String>, refresh_on: FsEventKind) -> PathIndex {
+        PathIndex {
+            keys_of,
+            refresh_on,
+            paths: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Drops everything and indexes the whole tree below `root`.
+    pub(crate) fn rebuild(&mut self, root: &Directory) {
+        self.paths.clear();
+        self.keys.clear();
+        self.insert_dir(&mut Vec::new(), root);
+    }
+
+    pub(crate) fn search(&self, key: &str) -> Vec<String> {
+        self.paths.get(key).map_or_else(Vec::new, |paths| {
+            paths
+                .iter()
+                .map(|parts| parts.iter().map(|p| format!("/{}", p)).collect())
+                .collect()
+        })
+    }
+
+    /// Brings the index in line with a change at the resolved `parts`.
+    pub(crate) fn update(
+        &mut self,
+        root: &Directory,
+        kind: &FsEventKind,
+        parts: &[String],
+        fold_case: bool,
+    ) {
+        match kind {
+            FsEventKind::Delete => self.remove_below(parts, fold_case),
+            FsEventKind::Rename { to } => {
+                let to = to
+                    .split('/')
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                for (old, keys) in self.take_below(parts, fold_case) {
+                    let mut moved = to.clone();
+                    moved.extend_from_slice(&old[parts.len()..]);
+                    self.insert_keys(moved, keys);
+                }
+            }
+            FsEventKind::Create => {
+                // A copy may have replaced a whole subtree.
+                self.remove_below(parts, fold_case);
+                if let Some(node) = node_at(root, parts) {
+                    self.insert(&mut parts.to_vec(), node);
+                }
+            }
+            kind if *kind == self.refresh_on => {
+                let Some(FSNode::File(file)) = node_at(root, parts) else {
+                    return;
+                };
+                if Arc::strong_count(&file.content) > 1 {
+                    // The change shows through every hard link to the file,
+                    // and the index cannot tell where those are.
+                    self.rebuild(root);
+                    return;
+                }
+                self.remove(parts);
+                self.insert_keys(parts.to_vec(), (self.keys_of)(file));
+            }
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, parts: &mut Vec<String>, node: &FSNode) {
+        match node {
+            FSNode::File(file) => self.insert_keys(parts.clone(), (self.keys_of)(file)),
+            FSNode::Directory(dir) => self.insert_dir(parts, dir),
+            FSNode::Symlink(_) => {}
+        }
+    }
+
+    fn insert_dir(&mut self, parts: &mut Vec<String>, dir: &Directory) {
+        for (name, node) in &dir.nodes {
+            parts.push(name.clone());
+            self.insert(parts, node);
+            parts.pop();
+        }
+    }
+
+    fn insert_keys(&mut self, parts: Vec<String>, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+        for key in &keys {
+            self.paths
+                .entry(key.clone())
+                .or_default()
+                .insert(parts.clone());
+        }
+        self.keys.insert(parts, keys);
+    }
+
+    fn remove(&mut self, parts: &[String]) -> Vec<String> {
+        let keys = self.keys.remove(parts).unwrap_or_default();
+        for key in &keys {
+            if let Some(paths) = self.paths.get_mut(key) {
+                paths.remove(parts);
+                if paths.is_empty() {
+                    self.paths.remove(key);
+                }
+            }
+        }
+        keys
+    }
+
+    fn remove_below(&mut self, prefix: &[String], fold_case: bool) {
+        self.take_below(prefix, fold_case);
+    }
+
+    /// Takes out every file at or below `prefix`, returning each with its
+    /// keys.
+    fn take_below(
+        &mut self,
+        prefix: &[String],
+        fold_case: bool,
+    ) -> Vec<(Vec<String>, Vec<String>)> {
+        let below =
+            self.keys
+                .keys()
+                .filter(|parts| {
+                    parts.len() >= prefix.len()
+                        && parts.iter().zip(prefix).all(|(a, b)| {
+                            a == b || fold_case && a.to_lowercase() == b.to_lowercase()
+                        })
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+        below
+            .into_iter()
+            .map(|parts| {
+                let keys = self.remove(&parts);
+                (parts, keys)
+            })
+            .collect()
+    }
+}
+
+fn node_at<'a>(root: &'a Directory, parts: &[String]) -> Option<&'a FSNode> {
+    let (name, parent) = parts.split_last()?;
+    let mut dir = root;
+    for part in parent {
+        match dir.nodes.get(part) {
+            Some(FSNode::Directory(subdir)) => dir = subdir,
+            _ => return None,
+        }
+    }
+    dir.nodes.get(name)
+}
+
+impl FileSystem {
+    /// Brings the indexes in line with a change at the resolved `parts`.
+    pub(crate) fn update_indexes(&mut self, kind: &FsEventKind, parts: &[String]) {
+        let fold_case = self.case_insensitive;
+        self.tag_index.update(&self.root, kind, parts, fold_case);
+        if let Some(index) = &mut self.text_index {
+            index.update(&self.root, kind, parts, fold_case);
+        }
+    }
+
+    /// Rebuilds the indexes from scratch, for changes that replace parts of
+    /// the tree without going through the change events.
+    pub(crate) fn rebuild_indexes(&mut self) {
+        self.tag_index.rebuild(&self.root);
+        if let Some(index) = &mut self.text_index {
+            index.rebuild(&self.root);
+        }
+    }
+
+    /// Turns the full-text index on or off. While on, every file is
+    /// indexed by the whitespace-separated words of its content and kept up
+    /// to date on every write, which makes `search_text` cheap at the cost
+    /// of memory and slower writes. Files that are not valid UTF-8 are
+    /// skipped.
+    pub fn set_text_index(&mut self, enabled: bool) {
+        match (enabled, &self.text_index) {
+            (true, None) => {
+                let mut index = PathIndex::words();
+                index.rebuild(&self.root);
+                self.text_index = Some(index);
+            }
+            (false, _) => self.text_index = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    /// Returns the files whose content contains `term` as a whole
+    /// whitespace-separated word, in walk order. Answered from the text
+    /// index when it is on; otherwise every file is scanned.
+    pub fn search_text(&self, term: &str) -> Result<Vec<String>, FsError> {
+        match &self.text_index {
+            Some(index) => Ok(index.search(term)),
+            None => {
+                let mut index = PathIndex::words();
+                index.rebuild(&self.root);
+                Ok(index.search(term))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileSystem;
+
+    /// Answers `search_by_tag` the slow way, by asking every file.
+    fn scan_for_tag(fs: &FileSystem, tag: &str) -> Vec<String> {
+        fs.walk("/")
+            .unwrap()
+            .into_iter()
+            .filter(|(path, is_dir)| {
+                !is_dir && fs.is_file(path) && fs.tags(path).unwrap().iter().any(|t| t == tag)
+            })
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    #[test]
+    fn tag_index_matches_scan_after_many_changes() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        for i in 0..20 {
+            let path = format!("/a/f{}", i);
+            fs.create(&path, None, false).unwrap();
+            if i % 2 == 0 {
+                fs.add_tag(&path, "even").unwrap();
+            }
+            if i % 3 == 0 {
+                fs.add_tag(&path, "three").unwrap();
+            }
+        }
+        fs.move_node("/a/f0", "/a/b/moved").unwrap();
+        fs.rename("/a/f2", "renamed").unwrap();
+        fs.delete("/a/f4").unwrap();
+        fs.remove_tag("/a/f6", "even").unwrap();
+        fs.copy("/a/b", "/", false).unwrap();
+        fs.create_hard_link("/a/f8", "/a/b/linked").unwrap();
+        fs.add_tag("/a/b/linked", "three").unwrap();
+        fs.trash("/a/f9").unwrap();
+        fs.create_symlink("/a/link", "/a/f10").unwrap();
+
+        for tag in ["even", "three", "missing"] {
+            assert_eq!(fs.search_by_tag(tag).unwrap(), scan_for_tag(&fs, tag));
+        }
+        assert!(fs
+            .search_by_tag("three")
+            .unwrap()
+            .contains(&"/a/f8".to_string()));
+    }
+
+    #[test]
+    fn tag_search_over_large_tree() {
+        let mut fs = FileSystem::new();
+        for dir in 0..50 {
+            fs.create(&format!("/d{}", dir), None, true).unwrap();
+            for file in 0..100 {
+                let path = format!("/d{}/f{}", dir, file);
+                fs.create(&path, None, false).unwrap();
+                if file == 0 {
+                    fs.add_tag(&path, "first").unwrap();
+                }
+            }
+        }
+        for _ in 0..1000 {
+            assert_eq!(fs.search_by_tag("first").unwrap().len(), 50);
+        }
+        assert_eq!(
+            fs.search_by_tag("first").unwrap(),
+            scan_for_tag(&fs, "first")
+        );
+    }
+
+    #[test]
+    fn text_index_follows_writes_through_symlinks() {
+        let mut fs = FileSystem::new();
+        fs.create("/doc", Some(b"old words".to_vec()), false)
+            .unwrap();
+        fs.create_symlink("/link", "/doc").unwrap();
+        fs.set_text_index(true);
+        fs.write_file("/link", b"hello there".to_vec(), false)
+            .unwrap();
+        assert_eq!(fs.search_text("hello").unwrap(), ["/doc"]);
+        assert!(fs.search_text("old").unwrap().is_empty());
+    }
+
+    #[test]
+    fn text_index_finds_shared_word() {
+        let mut fs = FileSystem::new();
+        fs.set_text_index(true);
+        fs.create("/a", Some(b"the quick fox".to_vec()), false)
+            .unwrap();
+        fs.create("/b", Some(b"a lazy fox".to_vec()), false)
+            .unwrap();
+        fs.create("/bin", Some(vec![0xff, 0xfe]), false).unwrap();
+        assert_eq!(fs.search_text("fox").unwrap(), ["/a", "/b"]);
+        fs.write_file("/a", b"no match".to_vec(), false).unwrap();
+        assert_eq!(fs.search_text("fox").unwrap(), ["/b"]);
+        fs.set_text_index(false);
+        assert_eq!(fs.search_text("fox").unwrap(), ["/b"]);
+    }
+}
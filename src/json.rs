@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AclEntry, Directory, FSNode, File, FileSystem, FsError, Metadata, Permissions, Symlink,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedMetadata {
+    #[serde(default)]
+    pub inode: u64,
+    pub created_at: u64,
+    pub modified_at: u64,
+    pub accessed_at: u64,
+    pub size: usize,
+    pub permissions: Permissions,
+    pub group_permissions: Permissions,
+    pub other_permissions: Permissions,
+    pub owner: String,
+    pub group: String,
+    pub is_read_only: bool,
+    pub is_hidden: bool,
+    pub mime_type: String,
+    pub tags: Vec<String>,
+    /// Extended attribute values, base64-encoded like file content.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<AclEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SerializedNode {
+    File {
+        name: String,
+        content: String,
+        metadata: SerializedMetadata,
+    },
+    Directory {
+        name: String,
+        metadata: SerializedMetadata,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quota: Option<usize>,
+        children: Vec<SerializedNode>,
+    },
+    Symlink {
+        name: String,
+        target: String,
+        metadata: SerializedMetadata,
+    },
+}
+
+impl FileSystem {
+    pub fn to_json(&self) -> Result<String, FsError> {
+        let root = serialize_directory(&self.root.name, &self.root);
+        serde_json::to_string_pretty(&root).map_err(|e| FsError::Serialization(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<FileSystem, FsError> {
+        let root: SerializedNode =
+            serde_json::from_str(json).map_err(|e| FsError::Serialization(e.to_string()))?;
+        let mut fs = FileSystem::new();
+        match deserialize_node(&fs, root)? {
+            FSNode::Directory(root) if root.name != "/" => Err(FsError::Serialization(format!(
+                "root directory must be named /, not {:?}",
+                root.name
+            ))),
+            FSNode::Directory(mut root) => {
+                // Documents written before inodes existed carry none, so
+                // number those nodes after the highest inode present.
+                let mut next_inode = max_inode(&root.metadata, &root.nodes).max(1) + 1;
+                if root.metadata.inode == 0 {
+                    root.metadata.inode = 1;
+                }
+                assign_missing_inodes(&mut root.nodes, &mut next_inode);
+                fs.root = root;
+                fs.next_inode = next_inode;
+                fs.rebuild_indexes();
+                Ok(fs)
+            }
+            _ => Err(FsError::Serialization(
+                "root node must be a directory".to_string(),
+            )),
+        }
+    }
+}
+
+fn max_inode(metadata: &Metadata, nodes: &BTreeMap<String, FSNode>) -> u64 {
+    nodes
+        .values()
+        .map(|node| match node {
+            FSNode::Directory(dir) => max_inode(&dir.metadata, &dir.nodes),
+            _ => node.metadata_ref().inode,
+        })
+        .fold(metadata.inode, u64::max)
+}
+
+fn assign_missing_inodes(nodes: &mut BTreeMap<String, FSNode>, next_inode: &mut u64) {
+    for node in nodes.values_mut() {
+        if node.metadata_ref().inode == 0 {
+            node.metadata().inode = *next_inode;
+            *next_inode += 1;
+        }
+        if let FSNode::Directory(dir) = node {
+            assign_missing_inodes(&mut dir.nodes, next_inode);
+        }
+    }
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+fn serialize_metadata(metadata: &Metadata) -> SerializedMetadata {
+    SerializedMetadata {
+        inode: metadata.inode,
+        created_at: to_millis(metadata.created_at),
+        modified_at: to_millis(metadata.modified_at),
+        accessed_at: to_millis(metadata.accessed_at),
+        size: metadata.size,
+        permissions: metadata.permissions,
+        group_permissions: metadata.group_permissions,
+        other_permissions: metadata.other_permissions,
+        owner: metadata.owner.clone(),
+        group: metadata.group.clone(),
+        is_read_only: metadata.is_read_only,
+        is_hidden: metadata.is_hidden,
+        mime_type: metadata.mime_type.clone(),
+        tags: metadata.tags.clone(),
+        xattrs: metadata
+            .xattrs
+            .iter()
+            .map(|(key, value)| (key.clone(), STANDARD.encode(value)))
+            .collect(),
+        acl: metadata.acl.clone(),
+    }
+}
+
+fn serialize_file(name: &str, file: &File) -> SerializedNode {
+    SerializedNode::File {
+        name: name.to_string(),
+        content: STANDARD.encode(&*file.content()),
+        metadata: serialize_metadata(&file.metadata),
+    }
+}
+
+fn serialize_symlink(name: &str, link: &Symlink) -> SerializedNode {
+    SerializedNode::Symlink {
+        name: name.to_string(),
+        target: link.target.clone(),
+        metadata: serialize_metadata(&link.metadata),
+    }
+}
+
+fn serialize_directory(name: &str, dir: &Directory) -> SerializedNode {
+    let children = dir
+        .nodes
+        .iter()
+        .map(|(name, node)| match node {
+            FSNode::File(file) => serialize_file(name, file),
+            FSNode::Directory(subdir) => serialize_directory(name, subdir),
+            FSNode::Symlink(link) => serialize_symlink(name, link),
+        })
+        .collect();
+    SerializedNode::Directory {
+        name: name.to_string(),
+        metadata: serialize_metadata(&dir.metadata),
+        quota: dir.quota,
+        children,
+    }
+}
+
+fn deserialize_metadata(metadata: SerializedMetadata) -> Result<Metadata, FsError> {
+    let xattrs = metadata
+        .xattrs
+        .into_iter()
+        .map(|(key, value)| {
+            let value = STANDARD.decode(value).map_err(|e| {
+                FsError::Serialization(format!("invalid value for xattr {}: {}", key, e))
+            })?;
+            Ok((key, value))
+        })
+        .collect::<Result<HashMap<_, _>, FsError>>()?;
+    Ok(Metadata {
+        inode: metadata.inode,
+        created_at: from_millis(metadata.created_at),
+        modified_at: from_millis(metadata.modified_at),
+        accessed_at: from_millis(metadata.accessed_at),
+        size: metadata.size,
+        permissions: metadata.permissions,
+        group_permissions: metadata.group_permissions,
+        other_permissions: metadata.other_permissions,
+        owner: metadata.owner,
+        group: metadata.group,
+        is_read_only: metadata.is_read_only,
+        is_hidden: metadata.is_hidden,
+        mime_type: metadata.mime_type,
+        tags: metadata.tags,
+        xattrs,
+        acl: metadata.acl,
+    })
+}
+
+/// Rebuilds a node, rejecting any entry below it whose name `fs` would
+/// not accept from `create`, such as `..` or one containing `/`.
+fn deserialize_node(fs: &FileSystem, node: SerializedNode) -> Result<FSNode, FsError> {
+    match node {
+        SerializedNode::File {
+            name,
+            content,
+            metadata,
+        } => {
+            let content = STANDARD.decode(content).map_err(|e| {
+                FsError::Serialization(format!("invalid content for {}: {}", name, e))
+            })?;
+            Ok(FSNode::File(File::new(
+                name,
+                content,
+                deserialize_metadata(metadata)?,
+            )))
+        }
+        SerializedNode::Directory {
+            name,
+            metadata,
+            quota,
+            children,
+        } => {
+            let mut nodes = BTreeMap::new();
+            for child in children {
+                let child = deserialize_node(fs, child)?;
+                let child_name = child.name().to_string();
+                fs.validate_name(&child_name).map_err(|_| {
+                    FsError::Serialization(format!(
+                        "invalid entry name {:?} in {}",
+                        child_name, name
+                    ))
+                })?;
+                if nodes.insert(child_name.clone(), child).is_some() {
+                    return Err(FsError::Serialization(format!(
+                        "duplicate entry {} in {}",
+                        child_name, name
+                    )));
+                }
+            }
+            Ok(FSNode::Directory(Directory {
+                name,
+                nodes,
+                metadata: deserialize_metadata(metadata)?,
+                quota,
+            }))
+        }
+        SerializedNode::Symlink {
+            name,
+            target,
+            metadata,
+        } => Ok(FSNode::Symlink(Symlink {
+            name,
+            target,
+            metadata: deserialize_metadata(metadata)?,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FileSystem, FsError};
+
+    #[test]
+    fn to_json_round_trips() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/docs").unwrap();
+        fs.create("/docs/a.txt", Some(b"hello".to_vec()), false)
+            .unwrap();
+        fs.add_tag("/docs/a.txt", "work").unwrap();
+        fs.set_xattr("/docs/a.txt", "user.k", b"v").unwrap();
+        fs.create_symlink("/link", "/docs/a.txt").unwrap();
+
+        let json = fs.to_json().unwrap();
+        let mut restored = FileSystem::from_json(&json).unwrap();
+        assert_eq!(restored.to_json().unwrap(), json);
+        assert_eq!(restored.read_file("/docs/a.txt").unwrap(), b"hello");
+        assert_eq!(restored.read_link("/link").unwrap(), "/docs/a.txt");
+        assert_eq!(restored.search_by_tag("work").unwrap(), ["/docs/a.txt"]);
+    }
+
+    #[test]
+    fn from_json_preserves_structure() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/c.txt", Some(b"c".to_vec()), false).unwrap();
+        fs.create("/a/d.bin", Some(vec![0, 255]), false).unwrap();
+        fs.chmod("/a/d.bin", "640").unwrap();
+        fs.set_quota("/a", 100).unwrap();
+        fs.create_symlink("/a/link", "b/c.txt").unwrap();
+        let restored = FileSystem::from_json(&fs.to_json().unwrap()).unwrap();
+        assert_eq!(restored, fs);
+    }
+
+    #[test]
+    fn from_json_rejects_invalid_names() {
+        let fs = FileSystem::new();
+        let json = fs.to_json().unwrap();
+        for name in ["../../escape.txt", "a/b", "..", "."] {
+            let file = format!(
+                r#"{{"type": "file", "name": {:?}, "content": "", "metadata": {}}}"#,
+                name,
+                root_metadata(&json)
+            );
+            let json = json.replace(r#""children": []"#, &format!(r#""children": [{}]"#, file));
+            assert!(
+                matches!(FileSystem::from_json(&json), Err(FsError::Serialization(_))),
+                "{} was accepted",
+                name
+            );
+        }
+        let json = json.replace(r#""name": "/""#, r#""name": "x""#);
+        assert!(FileSystem::from_json(&json).is_err());
+    }
+
+    /// The metadata object of the root in `json`, to reuse for a child.
+    fn root_metadata(json: &str) -> String {
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+        value["metadata"].to_string()
+    }
+}
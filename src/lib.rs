@@ -1,39 +1,370 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{btree_map, BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::SystemTime;
 
+use compression::{Content, ContentMut, ContentRef};
+use dedup::BlockStore;
+use disk::to_io_error;
+use index::PathIndex;
+use mount::Mount;
+use watch::Watcher;
+
+mod acl;
+#[cfg(feature = "tar")]
+mod archive;
+mod audit;
+mod compression;
+mod dedup;
+mod disk;
+mod handle;
+mod history;
+mod index;
+#[cfg(feature = "serde")]
+mod json;
+mod mount;
+mod session;
+mod shared;
+mod trash;
+mod watch;
+
+pub use acl::{Access, AclEntry};
+pub use audit::AuditEntry;
+pub use compression::{Compressor, NoopCompressor, RleCompressor};
+pub use dedup::DedupStats;
+pub use handle::{FileReader, FileWriter};
+#[cfg(feature = "serde")]
+pub use json::{SerializedMetadata, SerializedNode};
+pub use session::UserSession;
+pub use shared::SharedFileSystem;
+pub use watch::{FsEvent, FsEventKind, WatchHandle};
+
 #[derive(Debug, Clone)]
 struct Metadata {
+    /// Unique per node and never reused. Hard links share their source's
+    /// inode, and renames and moves keep it.
+    inode: u64,
     created_at: SystemTime,
     modified_at: SystemTime,
     accessed_at: SystemTime,
+    /// Byte length of a file's content. Directories always report 0; use
+    /// a traversal to aggregate the size of a subtree.
     size: usize,
     permissions: Permissions,
+    group_permissions: Permissions,
+    other_permissions: Permissions,
     owner: String,
     group: String,
     is_read_only: bool,
     is_hidden: bool,
     mime_type: String,
     tags: Vec<String>,
+    xattrs: HashMap<String, Vec<u8>>,
+    acl: Vec<AclEntry>,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Permissions {
-    read: bool,
-    write: bool,
-    execute: bool,
+/// Defaults to `rw-`, the owner permissions new nodes get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Permissions {
+        Permissions::new(true, true, false)
+    }
+}
+
+impl Permissions {
+    pub fn new(read: bool, write: bool, execute: bool) -> Permissions {
+        Permissions {
+            read,
+            write,
+            execute,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Permissions {
+        Permissions::new(bits & 0o4 != 0, bits & 0o2 != 0, bits & 0o1 != 0)
+    }
+
+    fn bits(&self) -> u32 {
+        u32::from(self.read) << 2 | u32::from(self.write) << 1 | u32::from(self.execute)
+    }
+
+    fn from_symbolic(triple: &str) -> Option<Permissions> {
+        let flag = |c: u8, expected: u8| match c {
+            b'-' => Some(false),
+            c if c == expected => Some(true),
+            _ => None,
+        };
+        match triple.as_bytes() {
+            [r, w, x] => Some(Permissions::new(
+                flag(*r, b'r')?,
+                flag(*w, b'w')?,
+                flag(*x, b'x')?,
+            )),
+            _ => None,
+        }
+    }
+
+    fn symbolic(&self) -> String {
+        [(self.read, 'r'), (self.write, 'w'), (self.execute, 'x')]
+            .iter()
+            .map(|(set, c)| if *set { *c } else { '-' })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetadataView {
+    pub owner: String,
+    pub group: String,
+    pub size: usize,
+    pub permissions: Permissions,
+    pub created_at: SystemTime,
+    pub modified_at: SystemTime,
+    pub accessed_at: SystemTime,
+    pub mime_type: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
+pub struct FileStat {
+    pub name: String,
+    pub size: usize,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub permissions: Permissions,
+    pub owner: String,
+    pub group: String,
+    pub mime_type: String,
+    pub tags: Vec<String>,
+    pub created_at: SystemTime,
+    pub modified_at: SystemTime,
+    pub accessed_at: SystemTime,
+}
+
+/// One entry of a directory listing, as returned by [`FileSystem::entries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: usize,
+    pub modified_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How [`FileSystem::copy_with_options`] treats existing entries and
+/// timestamps. The default neither overwrites nor preserves timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyOptions {
+    pub overwrite: bool,
+    pub preserve_timestamps: bool,
+}
+
+/// The result of [`FileSystem::tree_diff`]. Paths are relative to the two
+/// compared directories and sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    /// Paths present on both sides whose content, link target or node type
+    /// differs.
+    pub changed: Vec<String>,
+}
+
+/// The operations performed by [`FileSystem::sync`], as paths relative to
+/// the target directory. Each entry covers the whole subtree below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// Totals over a subtree, as returned by [`FileSystem::stats`]. The start
+/// directory itself is not counted, and `max_depth` is 1 for its direct
+/// children, or 0 when it is empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    pub file_count: usize,
+    pub dir_count: usize,
+    pub symlink_count: usize,
+    /// Sum of file sizes. A file reachable through several hard links
+    /// counts once per link.
+    pub total_bytes: usize,
+    pub max_depth: usize,
+}
+
+impl fmt::Display for FileStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dir {
+            write!(
+                f,
+                "Directory Name: {}\nSize: {}\nPermissions: {:?}\nOwner: {}",
+                self.name,
+                human_size(self.size),
+                self.permissions,
+                self.owner
+            )
+        } else if self.is_symlink {
+            write!(
+                f,
+                "Symlink Name: {}\nPermissions: {:?}\nOwner: {}",
+                self.name, self.permissions, self.owner
+            )
+        } else {
+            write!(
+                f,
+                "File Name: {}\nSize: {}\nPermissions: {:?}\nOwner: {}\nMIME Type: {}\nTags: {:?}",
+                self.name,
+                human_size(self.size),
+                self.permissions,
+                self.owner,
+                self.mime_type,
+                self.tags
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    NotAFile,
+    DirectoryNotEmpty,
+    PermissionDenied,
+    InvalidPath,
+    InvalidMode,
+    ReadOnly,
+    NotASymlink,
+    TooManySymlinks,
+    Serialization(String),
+    InvalidPattern(String),
+    InvalidUtf8,
+    QuotaExceeded,
+    Cancelled,
+    LimitExceeded,
+    Busy,
+    InvalidOffset,
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            FsError::Serialization(reason) => {
+                return write!(f, "Serialization failed: {}", reason);
+            }
+            FsError::InvalidPattern(reason) => {
+                return write!(f, "Invalid pattern: {}", reason);
+            }
+            FsError::NotFound => "File or directory not found.",
+            FsError::AlreadyExists => "File or directory already exists.",
+            FsError::NotADirectory => "Path is not a directory.",
+            FsError::NotAFile => "Path points to a directory.",
+            FsError::DirectoryNotEmpty => "Directory is not empty.",
+            FsError::PermissionDenied => "Permission denied.",
+            FsError::InvalidPath => "Invalid path provided.",
+            FsError::InvalidMode => "Invalid permission mode.",
+            FsError::ReadOnly => "File or directory is read-only.",
+            FsError::NotASymlink => "Path is not a symbolic link.",
+            FsError::TooManySymlinks => "Too many levels of symbolic links.",
+            FsError::InvalidUtf8 => "File content is not valid UTF-8.",
+            FsError::QuotaExceeded => "Not enough space left on the filesystem.",
+            FsError::Cancelled => "Operation cancelled.",
+            FsError::LimitExceeded => "Tree size limit exceeded.",
+            FsError::Busy => "Path is a mount point or contains one.",
+            FsError::InvalidOffset => "Offset or range lies past the end of the file.",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for FsError {}
+
+/// A file's name and metadata. The content buffer is shared between all
+/// hard links to the same file, and `sync_links` copies every metadata
+/// change from one link to the others. Cloning a `File` deep-copies the
+/// content, so the clone is an independent file rather than another link.
+#[derive(Debug)]
 struct File {
     name: String,
-    content: Vec<u8>,
+    content: Arc<RwLock<Content>>,
     metadata: Metadata,
 }
 
+impl File {
+    fn new(name: String, content: Vec<u8>, metadata: Metadata) -> File {
+        File {
+            name,
+            content: Arc::new(RwLock::new(Content::new(content))),
+            metadata,
+        }
+    }
+
+    fn content(&self) -> ContentRef<'_> {
+        ContentRef::new(self.raw_content())
+    }
+
+    fn content_mut(&self) -> ContentMut<'_> {
+        ContentMut::new(self.raw_content_mut())
+    }
+
+    /// The content as stored, which is compressed when compression is on.
+    fn raw_content(&self) -> RwLockReadGuard<'_, Content> {
+        self.content.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn raw_content_mut(&self) -> RwLockWriteGuard<'_, Content> {
+        self.content.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for File {
+    fn clone(&self) -> File {
+        File {
+            name: self.name.clone(),
+            content: Arc::new(RwLock::new(self.raw_content().clone())),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Directory {
     name: String,
-    nodes: HashMap<String, FSNode>,
+    /// Ordered by name, so listings and traversals come out sorted.
+    nodes: BTreeMap<String, FSNode>,
+    metadata: Metadata,
+    /// Upper bound on the content bytes stored anywhere below this directory.
+    quota: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Symlink {
+    name: String,
+    target: String,
     metadata: Metadata,
 }
 
@@ -41,49 +372,554 @@ struct Directory {
 enum FSNode {
     File(File),
     Directory(Directory),
+    Symlink(Symlink),
+}
+
+/// The timestamps a copied node takes from the clock rather than from its
+/// source.
+#[derive(Debug, Clone, Copy)]
+enum Refresh {
+    None,
+    Created,
+    All,
+}
+
+const MAX_SYMLINK_HOPS: usize = 40;
+const DEFAULT_MAX_NAME_LEN: usize = 255;
+
+/// Source of the timestamps recorded in node metadata.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The default [`Clock`], backed by `SystemTime::now`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Bounds on the shape of the tree, set with [`FileSystem::with_limits`].
+/// Depth counts path components, so `/a/b` is at depth 2. The root itself
+/// counts toward none of the limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_entries_per_dir: usize,
+    pub max_total_nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_depth: usize::MAX,
+            max_entries_per_dir: usize::MAX,
+            max_total_nodes: usize::MAX,
+        }
+    }
+}
+
+/// The entries below a directory in walk order, as `(path, is_dir)`. See
+/// [`FileSystem::walk_iter`].
+pub struct Walk<'a> {
+    /// The directories being walked, innermost last, each with its path.
+    stack: Vec<(String, btree_map::Iter<'a, String, FSNode>)>,
+}
+
+impl Iterator for Walk<'_> {
+    type Item = (String, bool);
+
+    fn next(&mut self) -> Option<(String, bool)> {
+        loop {
+            let (prefix, entries) = self.stack.last_mut()?;
+            let Some((name, node)) = entries.next() else {
+                self.stack.pop();
+                continue;
+            };
+            let path = format!("{}/{}", prefix, name);
+            if let FSNode::Directory(dir) = node {
+                self.stack.push((path.clone(), dir.nodes.iter()));
+                return Some((path, true));
+            }
+            return Some((path, false));
+        }
+    }
+}
+
+/// An opaque copy of a filesystem tree, its trash and its mounts, taken
+/// with [`FileSystem::snapshot`].
+pub struct Snapshot {
+    root: Directory,
+    trash: Vec<(String, FSNode)>,
+    mounts: Vec<Mount>,
 }
 
 pub struct FileSystem {
     root: Directory,
+    clock: Arc<dyn Clock>,
+    next_inode: u64,
+    capacity: Option<usize>,
+    compressor: Arc<dyn Compressor>,
+    blocks: BlockStore,
+    max_name_len: usize,
+    case_insensitive: bool,
+    limits: Limits,
+    /// Trashed nodes with the path each was trashed from.
+    trash: Vec<(String, FSNode)>,
+    history_limit: usize,
+    watchers: Vec<Watcher>,
+    audit_log: Option<Vec<AuditEntry>>,
+    mounts: Vec<Mount>,
+    frozen: bool,
+    default_permissions: Permissions,
+    umask: u32,
+    inherit_metadata: bool,
+    tag_index: PathIndex,
+    text_index: Option<PathIndex>,
+}
+
+impl Default for FileSystem {
+    fn default() -> FileSystem {
+        FileSystem::new()
+    }
+}
+
+impl Clone for FileSystem {
+    /// Copies the tree and every setting. Files that are hard links to each
+    /// other stay linked in the copy, but nothing is shared with the original.
+    fn clone(&self) -> FileSystem {
+        let Snapshot {
+            root,
+            trash,
+            mounts,
+        } = self.snapshot();
+        FileSystem {
+            root,
+            clock: Arc::clone(&self.clock),
+            next_inode: self.next_inode,
+            capacity: self.capacity,
+            compressor: Arc::clone(&self.compressor),
+            blocks: self.blocks.clone(),
+            max_name_len: self.max_name_len,
+            case_insensitive: self.case_insensitive,
+            limits: self.limits,
+            trash,
+            history_limit: self.history_limit,
+            watchers: Vec::new(),
+            audit_log: self.audit_log.clone(),
+            mounts,
+            frozen: self.frozen,
+            default_permissions: self.default_permissions,
+            umask: self.umask,
+            inherit_metadata: self.inherit_metadata,
+            tag_index: self.tag_index.clone(),
+            text_index: self.text_index.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileSystem")
+            .field("root", &self.root)
+            .field("capacity", &self.capacity)
+            .field("limits", &self.limits)
+            .field("case_insensitive", &self.case_insensitive)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for FileSystem {
+    /// Compares the trees: names, kinds, file content, link targets, quotas
+    /// and metadata other than inodes and timestamps. Settings, the trash
+    /// and version history are not compared.
+    fn eq(&self, other: &FileSystem) -> bool {
+        self.root.same_tree(&other.root, false)
+    }
 }
 
 impl FileSystem {
     pub fn new() -> FileSystem {
+        FileSystem::with_clock(Box::new(SystemClock))
+    }
+
+    /// Creates an empty filesystem that takes every timestamp from `clock`.
+    pub fn with_clock(clock: Box<dyn Clock>) -> FileSystem {
+        let clock: Arc<dyn Clock> = Arc::from(clock);
+        let now = clock.now();
         let root_metadata = Metadata {
-            created_at: SystemTime::now(),
-            modified_at: SystemTime::now(),
-            accessed_at: SystemTime::now(),
+            inode: 1,
+            created_at: now,
+            modified_at: now,
+            accessed_at: now,
             size: 0,
             permissions: Permissions {
                 read: true,
                 write: true,
                 execute: false,
             },
+            group_permissions: Permissions::new(true, false, false),
+            other_permissions: Permissions::new(true, false, false),
             owner: "root".to_string(),
             group: "root".to_string(),
             is_read_only: false,
             is_hidden: false,
             mime_type: "directory".to_string(),
             tags: vec![],
+            xattrs: HashMap::new(),
+            acl: Vec::new(),
         };
 
         FileSystem {
             root: Directory {
                 name: "/".to_string(),
-                nodes: HashMap::new(),
+                nodes: BTreeMap::new(),
                 metadata: root_metadata,
+                quota: None,
             },
+            clock,
+            next_inode: 2,
+            capacity: None,
+            compressor: Arc::new(RleCompressor),
+            blocks: BlockStore::default(),
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+            case_insensitive: false,
+            limits: Limits::default(),
+            trash: Vec::new(),
+            history_limit: 0,
+            watchers: Vec::new(),
+            audit_log: None,
+            mounts: Vec::new(),
+            frozen: false,
+            default_permissions: Permissions::default(),
+            umask: 0,
+            inherit_metadata: false,
+            tag_index: PathIndex::tags(),
+            text_index: None,
+        }
+    }
+
+    /// Creates an empty filesystem whose file contents may never total more
+    /// than `max_bytes`. Content shared by hard links is counted once.
+    pub fn with_capacity(max_bytes: usize) -> FileSystem {
+        let mut fs = FileSystem::new();
+        fs.capacity = Some(max_bytes);
+        fs
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Creates an empty filesystem whose tree never grows past `limits`.
+    /// Every operation that adds nodes or moves them deeper checks them.
+    pub fn with_limits(limits: Limits) -> FileSystem {
+        let mut fs = FileSystem::new();
+        fs.limits = limits;
+        fs
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Checks that a subtree `height` levels tall fits below the directory
+    /// at `parent` (already resolved), adding `new_nodes` nodes in total.
+    /// `new_entry` tells whether the subtree takes a new slot in `parent`.
+    fn check_limits(
+        &self,
+        parent: &[String],
+        height: usize,
+        new_entry: bool,
+        new_nodes: usize,
+    ) -> Result<(), FsError> {
+        let limits = &self.limits;
+        if parent.len() + height > limits.max_depth
+            || new_entry
+                && self.navigate_physical(parent)?.nodes.len() >= limits.max_entries_per_dir
+            || new_nodes > 0
+                && limits.max_total_nodes != usize::MAX
+                && self.root.node_count() + new_nodes > limits.max_total_nodes
+        {
+            return Err(FsError::LimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Sums the stored size of every file. Identical files count in full
+    /// even though their bytes are shared internally; see `dedup_stats`.
+    pub fn used_bytes(&self) -> usize {
+        let mut seen = HashSet::new();
+        self.root.content_bytes(&mut seen)
+    }
+
+    /// Estimates the heap and inline memory held by the tree: content,
+    /// version history, names, metadata strings and vectors, and a rough
+    /// allowance for map entries. Allocator overhead and the trash are
+    /// left out, so treat the result as an estimate rather than a measure.
+    /// Content shared by hard links or deduplication counts once.
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<FileSystem>() + self.root.memory_usage(&mut HashSet::new())
+    }
+
+    /// Counts the nodes below the directory at `start` in a single pass.
+    pub fn stats(&self, start: &str) -> Result<FsStats, FsError> {
+        let dir = self.navigate(&normalize(start)?)?;
+        let mut stats = FsStats::default();
+        dir.collect_stats(1, &mut stats);
+        Ok(stats)
+    }
+
+    /// Limits the content bytes stored below `dir_path` to `max_bytes`. A
+    /// write has to fit within every quota on its way up to the root, as
+    /// well as within the global capacity.
+    pub fn set_quota(&mut self, dir_path: &str, max_bytes: usize) -> Result<(), FsError> {
+        let parts = normalize(dir_path)?;
+        self.navigate_to_directory(&parts)?.quota = Some(max_bytes);
+        Ok(())
+    }
+
+    /// How large the file at `path` may grow before it hits the capacity or
+    /// an enclosing quota, or `None` when there is no limit.
+    fn room_for(&self, path: &str) -> Result<Option<usize>, FsError> {
+        let current = self.get_file(path)?.raw_content().data.len();
+        let parts = self.resolve(&normalize(path)?, true)?;
+        Ok(self.room_in(&parts[..parts.len() - 1], current))
+    }
+
+    /// How large an entry of `current` bytes inside the directory at `parts`
+    /// may grow. `parts` must already be resolved.
+    fn room_in(&self, parts: &[String], current: usize) -> Option<usize> {
+        let mut room = self
+            .capacity
+            .map(|capacity| (capacity + current).saturating_sub(self.used_bytes()));
+        let mut dir = &self.root;
+        let mut depth = 0;
+        loop {
+            if let Some(quota) = dir.quota {
+                let used = dir.content_bytes(&mut HashSet::new());
+                let left = (quota + current).saturating_sub(used);
+                room = Some(room.map_or(left, |room| room.min(left)));
+            }
+            match parts.get(depth).and_then(|part| dir.nodes.get(part)) {
+                Some(FSNode::Directory(subdir)) => dir = subdir,
+                _ => return room,
+            }
+            depth += 1;
+        }
+    }
+
+    fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Creates an empty filesystem that matches names case-insensitively,
+    /// like the default Windows and macOS filesystems. Names keep the case
+    /// they were created with, but `/A.txt` and `/a.txt` are the same entry.
+    pub fn new_case_insensitive() -> FileSystem {
+        let mut fs = FileSystem::new();
+        fs.case_insensitive = true;
+        fs
+    }
+
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// Makes the whole tree read-only until [`FileSystem::thaw`]: every
+    /// operation that would change a node fails with `PermissionDenied`.
+    /// Reads keep working, but `read_file` no longer updates `accessed_at`.
+    /// Settings such as limits or the history limit can still be changed.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn thaw(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn check_thawed(&self) -> Result<(), FsError> {
+        if self.frozen {
+            return Err(FsError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    /// Sets the owner permissions given to files and directories created
+    /// from now on. Group and other permissions stay read-only by default;
+    /// the umask applies to all three. Existing nodes, the root included,
+    /// keep their permissions.
+    pub fn set_default_permissions(&mut self, permissions: Permissions) {
+        self.default_permissions = permissions;
+    }
+
+    pub fn default_permissions(&self) -> Permissions {
+        self.default_permissions
+    }
+
+    /// Sets the octal mask of permission bits, such as `0o022`, cleared on
+    /// every file and directory created from now on.
+    pub fn set_umask(&mut self, umask: u32) {
+        self.umask = umask & 0o777;
+    }
+
+    pub fn umask(&self) -> u32 {
+        self.umask
+    }
+
+    /// Makes files and directories created from now on take their owner,
+    /// group and permissions from their parent directory instead of the
+    /// defaults. The umask still applies on top.
+    pub fn set_inherit_metadata(&mut self, inherit: bool) {
+        self.inherit_metadata = inherit;
+    }
+
+    pub fn inherit_metadata(&self) -> bool {
+        self.inherit_metadata
+    }
+
+    /// Metadata for a node created now inside the directory at the resolved
+    /// `parent`, with its permissions masked by the umask.
+    fn created_metadata(
+        &self,
+        parent: &[String],
+        inode: u64,
+        now: SystemTime,
+    ) -> Result<Metadata, FsError> {
+        let mut metadata = Metadata::new(inode, now);
+        metadata.permissions = self.default_permissions;
+        if self.inherit_metadata {
+            let inherited = &self.navigate_physical(parent)?.metadata;
+            metadata.owner = inherited.owner.clone();
+            metadata.group = inherited.group.clone();
+            metadata.permissions = inherited.permissions;
+            metadata.group_permissions = inherited.group_permissions;
+            metadata.other_permissions = inherited.other_permissions;
+        }
+        let mask = |permissions: Permissions, shift: u32| {
+            Permissions::from_bits(permissions.bits() & !(self.umask >> shift))
+        };
+        metadata.permissions = mask(metadata.permissions, 6);
+        metadata.group_permissions = mask(metadata.group_permissions, 3);
+        metadata.other_permissions = mask(metadata.other_permissions, 0);
+        Ok(metadata)
+    }
+
+    /// Sets the longest name, in bytes, that new entries may have.
+    pub fn set_max_name_len(&mut self, max_name_len: usize) {
+        self.max_name_len = max_name_len;
+    }
+
+    /// Rejects names that are empty, are `.` or `..`, contain `/` or a
+    /// control character, or are longer than the configured maximum.
+    fn validate_name(&self, name: &str) -> Result<(), FsError> {
+        if name.is_empty()
+            || name == "."
+            || name == ".."
+            || name.len() > self.max_name_len
+            || name.chars().any(|c| c == '/' || c.is_control())
+        {
+            return Err(FsError::InvalidPath);
+        }
+        Ok(())
+    }
+
+    fn allocate_inode(&mut self) -> u64 {
+        let inode = self.next_inode;
+        self.next_inode += 1;
+        inode
+    }
+
+    /// Copies the metadata of the file at `path` to every other hard link
+    /// to it, in the tree and in the trash. Called after each change to a
+    /// file's metadata, which is how links come to share it.
+    fn sync_links(&mut self, path: &str) {
+        let Ok(file) = self.get_file(path) else {
+            return;
+        };
+        if Arc::strong_count(&file.content) == 1 {
+            return;
+        }
+        let content = Arc::clone(&file.content);
+        let metadata = file.metadata.clone();
+        let mut update = |file: &mut File| {
+            if Arc::ptr_eq(&file.content, &content) {
+                file.metadata = metadata.clone();
+            }
+        };
+        let trashed = self.trash.iter_mut().map(|(_, node)| node);
+        for node in self.root.nodes.values_mut().chain(trashed) {
+            node.for_each_file_mut(&mut update);
+        }
+    }
+
+    fn reassign_inodes(&mut self, node: &mut FSNode) {
+        node.metadata().inode = self.allocate_inode();
+        if let FSNode::Directory(dir) = node {
+            for child in dir.nodes.values_mut() {
+                self.reassign_inodes(child);
+            }
         }
     }
 }
 
 impl Metadata {
-    fn update_accessed(&mut self) {
-        self.accessed_at = SystemTime::now();
+    /// Heap memory held by the strings and collections in the metadata.
+    fn memory_usage(&self) -> usize {
+        let string = std::mem::size_of::<String>();
+        self.owner.capacity()
+            + self.group.capacity()
+            + self.mime_type.capacity()
+            + self.tags.capacity() * string
+            + self.tags.iter().map(String::capacity).sum::<usize>()
+            + self.xattrs.capacity() * (string + std::mem::size_of::<Vec<u8>>() + 1)
+            + self
+                .xattrs
+                .iter()
+                .map(|(key, value)| key.capacity() + value.capacity())
+                .sum::<usize>()
+            + self.acl.capacity() * std::mem::size_of::<AclEntry>()
+            + self
+                .acl
+                .iter()
+                .map(|entry| entry.principal.capacity())
+                .sum::<usize>()
+    }
+
+    /// Compares everything but the inode, and the timestamps only when
+    /// `times` is set.
+    fn same_as(&self, other: &Metadata, times: bool) -> bool {
+        (!times
+            || self.created_at == other.created_at
+                && self.modified_at == other.modified_at
+                && self.accessed_at == other.accessed_at)
+            && self.size == other.size
+            && self.permissions == other.permissions
+            && self.group_permissions == other.group_permissions
+            && self.other_permissions == other.other_permissions
+            && self.owner == other.owner
+            && self.group == other.group
+            && self.is_read_only == other.is_read_only
+            && self.is_hidden == other.is_hidden
+            && self.mime_type == other.mime_type
+            && self.tags == other.tags
+            && self.xattrs == other.xattrs
+            && self.acl == other.acl
+    }
+
+    fn update_accessed(&mut self, now: SystemTime) {
+        self.accessed_at = now;
     }
 
-    fn update_modified(&mut self) {
-        self.modified_at = SystemTime::now();
+    fn update_modified(&mut self, now: SystemTime) {
+        self.modified_at = now;
     }
 }
 impl FileSystem {
@@ -92,23 +928,30 @@ impl FileSystem {
         path: &str,
         content: Option<Vec<u8>>,
         is_directory: bool,
-    ) -> Result<(), String> {
-        let mut parts = path
-            .split('/')
-            .filter(|p| !p.is_empty())
-            .collect::<Vec<_>>();
+    ) -> Result<(), FsError> {
+        let now = self.now();
+        let mut parts = normalize(path)?;
         if parts.is_empty() {
-            return Err("Invalid path provided.".to_string());
+            return Err(FsError::InvalidPath);
         }
 
         let name = parts.pop().unwrap().to_string();
-        let parent_dir = self.navigate_to_directory(&parts)?;
-
-        if parent_dir.nodes.contains_key(&name.clone()) {
-            return Err("File or directory already exists.".to_string());
+        self.validate_name(&name)?;
+        if self
+            .navigate(&parts)?
+            .contains(&name, self.case_insensitive)
+        {
+            return Err(FsError::AlreadyExists);
         }
-
-        let metadata = Metadata::default();
+        let resolved = self.resolve(&parts, true)?;
+        self.check_limits(&resolved, 1, true, 1)?;
+        if let Some(content) = &content {
+            let room = self.room_in(&resolved, 0);
+            check_room(room, 0, content.len())?;
+        }
+        let inode = self.allocate_inode();
+        let mut metadata = self.created_metadata(&resolved, inode, now)?;
+        let parent_dir = self.navigate_to_directory(&parts)?;
 
         if is_directory {
             let name_clone = name.clone();
@@ -116,268 +959,1732 @@ impl FileSystem {
                 name,
                 FSNode::Directory(Directory {
                     name: name_clone.clone(),
-                    nodes: HashMap::new(),
+                    nodes: BTreeMap::new(),
                     metadata,
+                    quota: None,
                 }),
             );
         } else {
             let name_clone = name.clone();
+            if content.is_some() {
+                metadata.mime_type = guess_mime_from_extension(&name).to_string();
+            }
+            let content = content.unwrap_or_default();
+            metadata.size = content.len();
             parent_dir.nodes.insert(
                 name,
-                FSNode::File(File {
-                    name: name_clone.clone(),
-                    content: content.unwrap_or_default(),
-                    metadata,
-                }),
+                FSNode::File(File::new(name_clone.clone(), content, metadata)),
             );
+            self.intern(path);
         }
+        self.notify(FsEventKind::Create, path);
 
         Ok(())
     }
 
-    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, String> {
-        let (dir, filename) = self.find_node(path)?;
-        match dir.nodes.get(filename) {
-            Some(FSNode::File(file)) => {
-                let mut metadata = file.metadata.clone();
-                metadata.update_accessed();
-                Ok(file.content.clone())
+    pub fn create_dir_all(&mut self, path: &str) -> Result<(), FsError> {
+        let parts = normalize(path)?;
+        for i in 1..=parts.len() {
+            let prefix = parts[..i].join("/");
+            if self.is_directory(&prefix) {
+                continue;
             }
-            Some(FSNode::Directory(_)) => Err("Path points to a directory.".to_string()),
-            None => Err("File not found.".to_string()),
-        }
-    }
-
-    pub fn write_file(&mut self, path: &str, content: Vec<u8>, append: bool) -> Result<(), String> {
-        let (dir, filename) = self.find_node(path)?;
-        let mut dir_node = dir.nodes.clone();
-        match dir_node.get_mut(filename) {
-            Some(FSNode::File(file)) => {
-                if append {
-                    file.content.extend(content);
-                } else {
-                    file.content = content;
-                }
-                file.metadata.update_modified();
-                Ok(())
+            if self.is_file(&prefix) {
+                return Err(FsError::NotADirectory);
             }
-            Some(FSNode::Directory(_)) => Err("Path points to a directory.".to_string()),
-            None => Err("File not found.".to_string()),
+            self.create(&prefix, None, true)?;
         }
+        Ok(())
     }
 
-    pub fn list_directory(&self, path: &str) -> Result<Vec<String>, String> {
-        let (dir, _) = self.find_node(path)?;
-        Ok(dir.nodes.keys().cloned().collect())
+    pub fn create_symlink(&mut self, link_path: &str, target_path: &str) -> Result<(), FsError> {
+        let now = self.now();
+        let (parent_dir, name) = self.find_node(link_path)?;
+        self.validate_name(&name)?;
+        if parent_dir.nodes.contains_key(&name) {
+            return Err(FsError::AlreadyExists);
+        }
+        let parent = normalize(link_path)?;
+        let parent = self.resolve(&parent[..parent.len() - 1], true)?;
+        self.check_limits(&parent, 1, true, 1)?;
+        let inode = self.allocate_inode();
+        let (parent_dir, name) = self.find_node_mut(link_path)?;
+        parent_dir.nodes.insert(
+            name.clone(),
+            FSNode::Symlink(Symlink {
+                name,
+                target: target_path.to_string(),
+                metadata: Metadata::new(inode, now),
+            }),
+        );
+        self.notify(FsEventKind::Create, link_path);
+        Ok(())
     }
 
-    fn navigate_to_directory(&mut self, parts: &[&str]) -> Result<&mut Directory, String> {
-        let mut current = &mut self.root;
-        for part in parts {
-            match current.nodes.get_mut(*part) {
-                Some(FSNode::Directory(dir)) => current = dir,
-                _ => return Err("Directory not found.".to_string()),
-            }
+    /// Makes `new_path` another name for the file at `existing`. The names
+    /// share content and metadata, inode included, so a write, permission
+    /// change or timestamp update through one shows through all of them.
+    pub fn create_hard_link(&mut self, existing: &str, new_path: &str) -> Result<(), FsError> {
+        if let Some(name) = normalize(new_path)?.last() {
+            self.validate_name(name)?;
         }
-        Ok(current)
+        let parent = normalize(new_path)?;
+        let parent = self.resolve(&parent[..parent.len().saturating_sub(1)], true)?;
+        self.check_limits(&parent, 1, true, 1)?;
+        let source = self.get_file(existing)?;
+        let link = File {
+            name: String::new(),
+            content: Arc::clone(&source.content),
+            metadata: source.metadata.clone(),
+        };
+        let (parent_dir, name) = self.find_node_mut(new_path)?;
+        if parent_dir.nodes.contains_key(&name) {
+            return Err(FsError::AlreadyExists);
+        }
+        parent_dir
+            .nodes
+            .insert(name.clone(), FSNode::File(File { name, ..link }));
+        self.notify(FsEventKind::Create, new_path);
+        Ok(())
     }
 
-    fn find_node(&self, path: &str) -> Result<(&Directory, &str), String> {
-        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
-        let filename = parts.last().ok_or_else(|| "Invalid path.".to_string())?;
-        let dir = *self.navigate_to_directory(&parts[..parts.len() - 1])?;
-        Ok((&dir, filename))
+    pub fn link_count(&self, path: &str) -> Result<usize, FsError> {
+        Ok(Arc::strong_count(&self.get_file(path)?.content))
     }
 
-    pub fn delete(&mut self, path: &str) -> Result<(), String> {
-        let mut parts = path
-            .split('/')
-            .filter(|p| !p.is_empty())
-            .collect::<Vec<_>>();
-        if parts.len() < 1 {
-            return Err("Invalid path provided.".to_string());
+    pub fn read_link(&self, path: &str) -> Result<String, FsError> {
+        match self.get_node(path)? {
+            FSNode::Symlink(link) => Ok(link.target.clone()),
+            _ => Err(FsError::NotASymlink),
         }
-        let name = parts.pop().unwrap();
-        let parent_dir = self.navigate_to_directory(&parts)?;
+    }
 
-        if let Some(node) = parent_dir.nodes.remove(name) {
-            match node {
-                FSNode::Directory(dir) => {
-                    if !dir.nodes.is_empty() {
-                        return Err("Directory is not empty.".to_string());
-                    }
-                }
-                _ => {}
-            }
-            Ok(())
-        } else {
-            Err("File or directory not found.".to_string())
+    /// Returns the file's content. Takes `&mut self` because every read
+    /// records the access in the file's `accessed_at` timestamp, unless the
+    /// tree is frozen.
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, FsError> {
+        if self.frozen {
+            return Ok(self.get_readable_file(path)?.content().clone());
+        }
+        let now = self.now();
+        let file = self.get_file_mut(path)?;
+        if !file.metadata.permissions.read {
+            return Err(FsError::PermissionDenied);
         }
+        file.metadata.update_accessed(now);
+        let content = file.content().clone();
+        self.sync_links(path);
+        Ok(content)
     }
 
-    pub fn update_file(
-        &mut self,
-        path: &str,
-        content: Vec<u8>,
+    /// Returns up to `len` bytes starting at `offset`, clamped to the end of
+    /// the file. Unlike `read_file` this does not touch `accessed_at`.
+    pub fn read_range(&self, path: &str, offset: usize, len: usize) -> Result<Vec<u8>, FsError> {
+        let file = self.get_readable_file(path)?;
+        let content = file.content();
+        let start = offset.min(content.len());
+        let end = start.saturating_add(len).min(content.len());
+        Ok(content[start..end].to_vec())
+    }
+
+    /// Returns the 64-bit FNV-1a hash of a file's content. The value only
+    /// depends on the bytes, so it is stable across runs and platforms.
+    pub fn checksum(&self, path: &str) -> Result<u64, FsError> {
+        let file = self.get_readable_file(path)?;
+        Ok(fnv1a(&file.content()))
+    }
+
+    pub fn verify_checksum(&self, path: &str, expected: u64) -> Result<bool, FsError> {
+        Ok(self.checksum(path)? == expected)
+    }
+
+    /// Returns the content decoded as UTF-8. Like `read_range`, this does
+    /// not touch `accessed_at`.
+    pub fn read_to_string(&self, path: &str) -> Result<String, FsError> {
+        let file = self.get_readable_file(path)?;
+        let content = file.content();
+        std::str::from_utf8(&content)
+            .map(str::to_string)
+            .map_err(|_| FsError::InvalidUtf8)
+    }
+
+    /// Replaces the content of the file at `path` with `s`, creating the
+    /// file first if it does not exist.
+    pub fn write_str(&mut self, path: &str, s: &str) -> Result<(), FsError> {
+        if self.exists(path) {
+            self.write_file(path, s.as_bytes().to_vec(), false)
+        } else {
+            self.create(path, Some(s.as_bytes().to_vec()), false)
+        }
+    }
+
+    /// Splits the content on `\n`. A trailing newline does not produce an
+    /// extra empty line, so `"a\nb"` and `"a\nb\n"` both read as two lines.
+    pub fn read_lines(&self, path: &str) -> Result<Vec<String>, FsError> {
+        let content = self.read_to_string(path)?;
+        let content = content.strip_suffix('\n').unwrap_or(&content);
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(content.split('\n').map(str::to_string).collect())
+    }
+
+    /// Appends `line` and a newline. If the file does not already end in a
+    /// newline, one is added first so the new line starts on its own.
+    pub fn append_line(&mut self, path: &str, line: &str) -> Result<(), FsError> {
+        self.edit(path, |content| {
+            if content.last().is_some_and(|&byte| byte != b'\n') {
+                content.push(b'\n');
+            }
+            content.extend_from_slice(line.as_bytes());
+            content.push(b'\n');
+        })
+    }
+
+    /// Inserts `data` before the byte at `offset`, shifting the rest of the
+    /// content back. An `offset` equal to the length appends.
+    pub fn insert_at(&mut self, path: &str, offset: usize, data: &[u8]) -> Result<(), FsError> {
+        if offset > self.get_file(path)?.content().len() {
+            return Err(FsError::InvalidOffset);
+        }
+        self.edit(path, |content| {
+            content.splice(offset..offset, data.iter().copied());
+        })
+    }
+
+    /// Removes `len` bytes starting at `offset`, shifting the rest of the
+    /// content forward. The whole range must lie within the file.
+    pub fn remove_range(&mut self, path: &str, offset: usize, len: usize) -> Result<(), FsError> {
+        let end = offset.checked_add(len).ok_or(FsError::InvalidOffset)?;
+        if end > self.get_file(path)?.content().len() {
+            return Err(FsError::InvalidOffset);
+        }
+        self.edit(path, |content| {
+            content.drain(offset..end);
+        })
+    }
+
+    /// Compares two files byte by byte and returns `(offset, byte_a, byte_b)`
+    /// for every position that differs. When one file is longer, each of its
+    /// trailing bytes is paired with `0` for the shorter file.
+    pub fn diff(&self, path_a: &str, path_b: &str) -> Result<Vec<(usize, u8, u8)>, FsError> {
+        let a = self.get_readable_file(path_a)?.content();
+        let b = self.get_readable_file(path_b)?.content();
+        let len = a.len().max(b.len());
+        Ok((0..len)
+            .filter_map(|i| {
+                let byte_a = a.get(i).copied().unwrap_or(0);
+                let byte_b = b.get(i).copied().unwrap_or(0);
+                (i >= a.len() || i >= b.len() || byte_a != byte_b).then_some((i, byte_a, byte_b))
+            })
+            .collect())
+    }
+
+    pub fn write_file(
+        &mut self,
+        path: &str,
+        content: Vec<u8>,
         append: bool,
-    ) -> Result<(), String> {
+    ) -> Result<(), FsError> {
+        let now = self.now();
+        let room = self.room_for(path)?;
+        let history_limit = self.history_limit;
+        let file = self.get_file_mut(path)?;
+        if file.metadata.is_read_only {
+            return Err(FsError::ReadOnly);
+        }
+        if !file.metadata.permissions.write {
+            return Err(FsError::PermissionDenied);
+        }
+        let mut buffer = file.content_mut();
+        let new_len = if append {
+            buffer.len() + content.len()
+        } else {
+            content.len()
+        };
+        check_room(room, buffer.len(), new_len)?;
+        let previous = (history_limit > 0).then(|| buffer.clone());
+        if append {
+            buffer.extend(content);
+        } else {
+            *buffer = content;
+        }
+        let size = buffer.len();
+        drop(buffer);
+        if let Some(previous) = previous {
+            file.push_version(previous, history_limit);
+        }
+        file.metadata.size = size;
+        file.metadata.update_modified(now);
+        self.intern(path);
+        self.notify(FsEventKind::Modify, path);
+        Ok(())
+    }
+
+    /// Hands the file's content buffer to `f` for in-place changes, then
+    /// refreshes `size` and `modified_at`.
+    pub fn edit<F>(&mut self, path: &str, f: F) -> Result<(), FsError>
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let now = self.now();
+        let room = self.room_for(path)?;
+        let file = self.get_file_mut(path)?;
+        if file.metadata.is_read_only {
+            return Err(FsError::ReadOnly);
+        }
+        if !file.metadata.permissions.write {
+            return Err(FsError::PermissionDenied);
+        }
+        let mut buffer = file.content_mut();
+        // Keep the original only when there is a limit to roll back from.
+        let original = room.map(|_| buffer.clone());
+        f(&mut buffer);
+        if let Some(original) = original {
+            if let Err(err) = check_room(room, original.len(), buffer.len()) {
+                *buffer = original;
+                return Err(err);
+            }
+        }
+        let size = buffer.len();
+        drop(buffer);
+        file.metadata.size = size;
+        file.metadata.update_modified(now);
+        self.intern(path);
+        self.notify(FsEventKind::Modify, path);
+        Ok(())
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.is_file(path) || self.is_directory(path)
+    }
+
+    pub fn is_file(&self, path: &str) -> bool {
+        self.get_file(path).is_ok()
+    }
+
+    pub fn is_directory(&self, path: &str) -> bool {
+        match normalize(path) {
+            Ok(parts) => self.navigate(&parts).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn truncate(&mut self, path: &str, new_len: usize) -> Result<(), FsError> {
+        let now = self.now();
+        let room = self.room_for(path)?;
+        let file = self.get_file_mut(path)?;
+        if file.metadata.is_read_only {
+            return Err(FsError::ReadOnly);
+        }
+        if !file.metadata.permissions.write {
+            return Err(FsError::PermissionDenied);
+        }
+        let mut buffer = file.content_mut();
+        check_room(room, buffer.len(), new_len)?;
+        buffer.resize(new_len, 0);
+        drop(buffer);
+        file.metadata.size = new_len;
+        file.metadata.update_modified(now);
+        self.intern(path);
+        self.notify(FsEventKind::Modify, path);
+        Ok(())
+    }
+
+    /// Returns the names in the directory at `path`, sorted by name. Every
+    /// listing, search and traversal visits entries in this same order, so
+    /// results never depend on insertion order.
+    pub fn list_directory(&self, path: &str) -> Result<Vec<String>, FsError> {
+        let parts = normalize(path)?;
+        let dir = self.navigate(&parts)?;
+        Ok(dir.nodes.keys().cloned().collect())
+    }
+
+    /// The number of entries directly inside the directory at `path`.
+    pub fn entry_count(&self, path: &str) -> Result<usize, FsError> {
+        Ok(self.navigate(&normalize(path)?)?.nodes.len())
+    }
+
+    pub fn is_empty_dir(&self, path: &str) -> Result<bool, FsError> {
+        Ok(self.navigate(&normalize(path)?)?.nodes.is_empty())
+    }
+
+    /// Lists the directory at `path` with basic metadata, sorted by name.
+    pub fn entries(&self, path: &str) -> Result<Vec<DirEntry>, FsError> {
+        let parts = normalize(path)?;
+        let dir = self.navigate(&parts)?;
+        let entries = dir
+            .nodes
+            .iter()
+            .map(|(name, node)| DirEntry {
+                name: name.clone(),
+                is_dir: matches!(node, FSNode::Directory(_)),
+                size: node.size(),
+                modified_at: node.metadata_ref().modified_at,
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Lists the directory at `path` ordered by `by`. Directories always come
+    /// before other entries whatever the order, and entries that compare
+    /// equal fall back to ascending name order.
+    pub fn list_sorted(
+        &self,
+        path: &str,
+        by: SortKey,
+        order: Order,
+    ) -> Result<Vec<String>, FsError> {
+        let mut entries = self.entries(path)?;
+        entries.sort_by(|a, b| {
+            let ordering = match by {
+                SortKey::Name => a.name.cmp(&b.name),
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Modified => a.modified_at.cmp(&b.modified_at),
+            };
+            let ordering = match order {
+                Order::Asc => ordering,
+                Order::Desc => ordering.reverse(),
+            };
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then(ordering)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(entries.into_iter().map(|entry| entry.name).collect())
+    }
+
+    /// Like `list_directory`, but leaves out entries flagged hidden via
+    /// `set_hidden` as well as dotfiles.
+    pub fn list_directory_visible(&self, path: &str) -> Result<Vec<String>, FsError> {
+        let parts = normalize(path)?;
+        let dir = self.navigate(&parts)?;
+        Ok(dir
+            .nodes
+            .iter()
+            .filter(|(name, node)| !name.starts_with('.') && !node.metadata_ref().is_hidden)
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    pub fn set_read_only(&mut self, path: &str, ro: bool) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.is_read_only = ro;
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn set_hidden(&mut self, path: &str, hidden: bool) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.is_hidden = hidden;
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    /// Every change to the tree reaches its nodes through here or
+    /// `metadata_at_mut`, so both refuse while the tree is frozen.
+    fn navigate_to_directory(&mut self, parts: &[String]) -> Result<&mut Directory, FsError> {
+        self.check_thawed()?;
+        let parts = self.resolve(parts, true)?;
+        let mut current = &mut self.root;
+        for part in &parts {
+            match current.nodes.get_mut(part) {
+                Some(FSNode::Directory(dir)) => current = dir,
+                Some(_) => return Err(FsError::NotADirectory),
+                None => return Err(FsError::NotFound),
+            }
+        }
+        Ok(current)
+    }
+
+    fn navigate(&self, parts: &[String]) -> Result<&Directory, FsError> {
+        let parts = self.resolve(parts, true)?;
+        self.navigate_physical(&parts)
+    }
+
+    /// Fails with `NotFound` for a missing component and `NotADirectory` for
+    /// one that exists but is a file or a link.
+    fn navigate_physical(&self, parts: &[String]) -> Result<&Directory, FsError> {
+        let mut current = &self.root;
+        for part in parts {
+            match current.nodes.get(part) {
+                Some(FSNode::Directory(dir)) => current = dir,
+                Some(_) => return Err(FsError::NotADirectory),
+                None => return Err(FsError::NotFound),
+            }
+        }
+        Ok(current)
+    }
+
+    /// Rewrites `parts` into a path free of symbolic links by following
+    /// every link met along the way, including the final component when
+    /// `follow_last` is set. Relative link targets resolve against the
+    /// directory holding the link. Gives up with `TooManySymlinks` after
+    /// `MAX_SYMLINK_HOPS` links so cycles cannot loop forever.
+    fn resolve(&self, parts: &[String], follow_last: bool) -> Result<Vec<String>, FsError> {
+        let mut resolved: Vec<String> = Vec::new();
+        let mut pending: VecDeque<String> = parts.iter().cloned().collect();
+        let mut hops = 0;
+        while let Some(part) = pending.pop_front() {
+            match part.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    resolved.pop().ok_or(FsError::InvalidPath)?;
+                    continue;
+                }
+                _ => {}
+            }
+            let dir = self.navigate_physical(&resolved)?;
+            let part = dir.entry_name(&part, self.case_insensitive);
+            match dir.nodes.get(&part) {
+                Some(FSNode::Symlink(link)) if follow_last || !pending.is_empty() => {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(FsError::TooManySymlinks);
+                    }
+                    if link.target.starts_with('/') {
+                        resolved.clear();
+                    }
+                    for component in link.target.split('/').rev() {
+                        pending.push_front(component.to_string());
+                    }
+                }
+                _ => resolved.push(part),
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn find_node(&self, path: &str) -> Result<(&Directory, String), FsError> {
+        let mut parts = normalize(path)?;
+        let filename = parts.pop().ok_or(FsError::InvalidPath)?;
+        let dir = self.navigate(&parts)?;
+        let filename = dir.entry_name(&filename, self.case_insensitive);
+        Ok((dir, filename))
+    }
+
+    fn find_node_mut(&mut self, path: &str) -> Result<(&mut Directory, String), FsError> {
+        let mut parts = normalize(path)?;
+        let filename = parts.pop().ok_or(FsError::InvalidPath)?;
+        let fold_case = self.case_insensitive;
+        let dir = self.navigate_to_directory(&parts)?;
+        let filename = dir.entry_name(&filename, fold_case);
+        Ok((dir, filename))
+    }
+
+    fn get_node(&self, path: &str) -> Result<&FSNode, FsError> {
         let (dir, filename) = self.find_node(path)?;
-        let mut dir_nodes = dir.nodes.clone();
-        if let Some(FSNode::File(file)) = dir_nodes.get_mut(filename) {
-            if !file.metadata.permissions.write {
-                return Err("Write permission denied.".to_string());
+        dir.nodes.get(&filename).ok_or(FsError::NotFound)
+    }
+
+    fn get_node_mut(&mut self, path: &str) -> Result<&mut FSNode, FsError> {
+        let (dir, filename) = self.find_node_mut(path)?;
+        dir.nodes.get_mut(&filename).ok_or(FsError::NotFound)
+    }
+
+    /// The metadata of the node at `path`, which unlike `get_node` may be
+    /// the root.
+    fn metadata_at(&self, path: &str) -> Result<&Metadata, FsError> {
+        if normalize(path)?.is_empty() {
+            return Ok(&self.root.metadata);
+        }
+        Ok(self.get_node(path)?.metadata_ref())
+    }
+
+    fn metadata_at_mut(&mut self, path: &str) -> Result<&mut Metadata, FsError> {
+        self.check_thawed()?;
+        if normalize(path)?.is_empty() {
+            return Ok(&mut self.root.metadata);
+        }
+        Ok(self.get_node_mut(path)?.metadata())
+    }
+
+    fn physical_path(&self, path: &str) -> Result<String, FsError> {
+        Ok(self.resolve(&normalize(path)?, true)?.join("/"))
+    }
+
+    fn get_file(&self, path: &str) -> Result<&File, FsError> {
+        let path = self.physical_path(path)?;
+        match self.get_node(&path)? {
+            FSNode::File(file) => Ok(file),
+            _ => Err(FsError::NotAFile),
+        }
+    }
+
+    fn get_readable_file(&self, path: &str) -> Result<&File, FsError> {
+        let file = self.get_file(path)?;
+        if !file.metadata.permissions.read {
+            return Err(FsError::PermissionDenied);
+        }
+        Ok(file)
+    }
+
+    fn get_file_mut(&mut self, path: &str) -> Result<&mut File, FsError> {
+        let path = self.physical_path(path)?;
+        match self.get_node_mut(&path)? {
+            FSNode::File(file) => Ok(file),
+            _ => Err(FsError::NotAFile),
+        }
+    }
+
+    /// Removes the file, link or empty directory at `path`. The root can
+    /// never be deleted.
+    pub fn delete(&mut self, path: &str) -> Result<(), FsError> {
+        let mut parts = normalize(path)?;
+        if parts.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        self.check_unmounted(path)?;
+        let name = parts.pop().unwrap();
+        let fold_case = self.case_insensitive;
+        let parent_dir = self.navigate_to_directory(&parts)?;
+        let name = parent_dir.entry_name(&name, fold_case);
+
+        match parent_dir.nodes.get(&name) {
+            Some(node) if node.metadata_ref().is_read_only => Err(FsError::ReadOnly),
+            Some(FSNode::Directory(dir)) if !dir.nodes.is_empty() => {
+                Err(FsError::DirectoryNotEmpty)
+            }
+            Some(_) => {
+                parent_dir.nodes.remove(&name);
+                self.notify(FsEventKind::Delete, path);
+                Ok(())
+            }
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    pub fn delete_recursive(&mut self, path: &str) -> Result<(), FsError> {
+        let mut parts = normalize(path)?;
+        if parts.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        self.check_unmounted(path)?;
+        let name = parts.pop().unwrap();
+        let fold_case = self.case_insensitive;
+        let parent_dir = self.navigate_to_directory(&parts)?;
+        let name = parent_dir.entry_name(&name, fold_case);
+
+        match parent_dir.nodes.get(&name) {
+            Some(node) if node.metadata_ref().is_read_only => Err(FsError::ReadOnly),
+            Some(_) => {
+                parent_dir.nodes.remove(&name);
+                self.notify(FsEventKind::Delete, path);
+                Ok(())
             }
+            None => Err(FsError::NotFound),
+        }
+    }
 
-            if append {
-                file.content.extend(content);
-            } else {
-                file.content = content;
+    /// Deletes every node matching the glob `pattern` and returns how many
+    /// were removed. Matching directories that are not empty are skipped
+    /// unless `recursive` is set, in which case they go with everything
+    /// below them. Matches are deleted deepest first, so a directory whose
+    /// entries all match counts as empty by the time its turn comes. Either
+    /// every deletion succeeds or the tree is left as it was.
+    pub fn delete_glob(&mut self, pattern: &str, recursive: bool) -> Result<usize, FsError> {
+        let mut paths = self.glob(pattern)?;
+        paths.sort_by_key(|path| Reverse(path.matches('/').count()));
+        let mut deleted = 0;
+        self.transaction(|fs| {
+            for path in paths {
+                match fs.get_node(&path)? {
+                    FSNode::Directory(dir) if !dir.nodes.is_empty() && !recursive => continue,
+                    _ => fs.delete_recursive(&path)?,
+                }
+                deleted += 1;
             }
-            file.metadata.update_modified();
             Ok(())
+        })?;
+        Ok(deleted)
+    }
+
+    pub fn update_file(
+        &mut self,
+        path: &str,
+        content: Vec<u8>,
+        append: bool,
+    ) -> Result<(), FsError> {
+        let now = self.now();
+        let room = self.room_for(path)?;
+        let history_limit = self.history_limit;
+        let file = self.get_file_mut(path)?;
+        if file.metadata.is_read_only {
+            return Err(FsError::ReadOnly);
+        }
+        if !file.metadata.permissions.write {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let mut buffer = file.content_mut();
+        let new_len = if append {
+            buffer.len() + content.len()
+        } else {
+            content.len()
+        };
+        check_room(room, buffer.len(), new_len)?;
+        let previous = (history_limit > 0).then(|| buffer.clone());
+        if append {
+            buffer.extend(content);
         } else {
-            Err("File not found.".to_string())
+            *buffer = content;
+        }
+        let size = buffer.len();
+        drop(buffer);
+        if let Some(previous) = previous {
+            file.push_version(previous, history_limit);
         }
+        file.metadata.size = size;
+        file.metadata.update_modified(now);
+        self.intern(path);
+        self.notify(FsEventKind::Modify, path);
+        Ok(())
     }
 
     pub fn change_permissions(
         &mut self,
         path: &str,
         permissions: Permissions,
-    ) -> Result<(), String> {
-        let (dir, filename) = self.find_node(path)?;
-        if let Some(node) = dir.nodes.clone().get_mut(filename) {
-            node.metadata().permissions = permissions;
-            node.metadata().update_modified();
-            Ok(())
-        } else {
-            Err("File or directory not found.".to_string())
+    ) -> Result<(), FsError> {
+        let now = self.now();
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.permissions = permissions;
+        metadata.update_modified(now);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    /// Sets owner, group and other permissions from an octal mode such as
+    /// `"755"` or a symbolic one such as `"rwxr-xr-x"`.
+    pub fn chmod(&mut self, path: &str, mode: &str) -> Result<(), FsError> {
+        let now = self.now();
+        let [owner, group, other] = parse_mode(mode)?;
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.permissions = owner;
+        metadata.group_permissions = group;
+        metadata.other_permissions = other;
+        metadata.update_modified(now);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn mode_string(&self, path: &str) -> Result<String, FsError> {
+        let metadata = self.metadata_at(path)?;
+        Ok(format!(
+            "{}{}{}",
+            metadata.permissions.symbolic(),
+            metadata.group_permissions.symbolic(),
+            metadata.other_permissions.symbolic()
+        ))
+    }
+
+    /// Creates an empty file at `path`, or sets `modified_at` and
+    /// `accessed_at` to now if something already exists there. Like Unix
+    /// `touch`, symbolic links are followed.
+    pub fn touch(&mut self, path: &str) -> Result<(), FsError> {
+        if !self.exists(path) {
+            return self.create(path, None, false);
+        }
+        let now = self.now();
+        let path = self.physical_path(path)?;
+        let metadata = self.get_node_mut(&path)?.metadata();
+        metadata.update_modified(now);
+        metadata.update_accessed(now);
+        self.notify(FsEventKind::Metadata, &path);
+        Ok(())
+    }
+
+    pub fn set_owner(&mut self, path: &str, owner: &str) -> Result<(), FsError> {
+        let now = self.now();
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.owner = owner.to_string();
+        metadata.update_modified(now);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn set_group(&mut self, path: &str, group: &str) -> Result<(), FsError> {
+        let now = self.now();
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.group = group.to_string();
+        metadata.update_modified(now);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn add_tag(&mut self, path: &str, tag: &str) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        if !metadata.tags.iter().any(|t| t == tag) {
+            metadata.tags.push(tag.to_string());
+        }
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn remove_tag(&mut self, path: &str, tag: &str) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.tags.retain(|t| t != tag);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    /// Adds `tag` to every file beneath `start` and returns how many did not
+    /// have it yet. Directories and symbolic links are left alone, matching
+    /// `search_by_tag`, which only reports files.
+    pub fn tag_all(&mut self, start: &str, tag: &str) -> Result<usize, FsError> {
+        let paths = self.files_where(start, |file| !file.metadata.tags.iter().any(|t| t == tag))?;
+        for path in &paths {
+            self.add_tag(path, tag)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Removes `tag` from every file beneath `start` and returns how many
+    /// had it.
+    pub fn untag_all(&mut self, start: &str, tag: &str) -> Result<usize, FsError> {
+        let paths = self.files_where(start, |file| file.metadata.tags.iter().any(|t| t == tag))?;
+        for path in &paths {
+            self.remove_tag(path, tag)?;
         }
+        Ok(paths.len())
+    }
+
+    pub fn tags(&self, path: &str) -> Result<Vec<String>, FsError> {
+        Ok(self.metadata_at(path)?.tags.clone())
+    }
+
+    pub fn set_xattr(&mut self, path: &str, key: &str, value: &[u8]) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.xattrs.insert(key.to_string(), value.to_vec());
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn get_xattr(&self, path: &str, key: &str) -> Result<Option<Vec<u8>>, FsError> {
+        Ok(self.metadata_at(path)?.xattrs.get(key).cloned())
+    }
+
+    /// Returns the attribute names set on the node, sorted.
+    pub fn list_xattr(&self, path: &str) -> Result<Vec<String>, FsError> {
+        let mut keys = self
+            .metadata_at(path)?
+            .xattrs
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        Ok(keys)
+    }
+
+    pub fn remove_xattr(&mut self, path: &str, key: &str) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.xattrs.remove(key);
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    /// Returns the files tagged `tag`, in walk order. Answered from an
+    /// index kept up to date on every change, so the cost depends on the
+    /// number of results rather than the size of the tree.
+    pub fn search_by_tag(&self, tag: &str) -> Result<Vec<String>, FsError> {
+        Ok(self.tag_index.search(tag))
     }
 
-    pub fn search_by_tag(&self, tag: &str) -> Result<Vec<String>, String> {
+    pub fn search_by_owner(&self, owner: &str) -> Result<Vec<String>, FsError> {
         let mut results = Vec::new();
-        self.search_by_tag_recursive(&self.root, tag, &mut results);
+        self.search_by_owner_recursive(&self.root, "", owner, &mut results);
         Ok(results)
     }
 
-    
-    fn search_by_tag_recursive(&self, dir: &Directory, tag: &str, results: &mut Vec<String>) {
+    fn search_by_owner_recursive(
+        &self,
+        dir: &Directory,
+        prefix: &str,
+        owner: &str,
+        results: &mut Vec<String>,
+    ) {
         for (name, node) in &dir.nodes {
+            let path = format!("{}/{}", prefix, name);
             match node {
-                FSNode::File(file) if file.metadata.tags.contains(&tag.to_string()) => {
-                    results.push(format!("{}/{}", dir.name, file.name));
+                FSNode::File(file) if file.metadata.owner == owner => {
+                    results.push(path);
                 }
                 FSNode::Directory(subdir) => {
-                    self.search_by_tag_recursive(subdir, tag, results);
+                    self.search_by_owner_recursive(subdir, &path, owner, results);
                 }
                 _ => {}
             }
         }
     }
 
-    
-    pub fn search_by_mime_type(&self, mime_type: &str) -> Result<Vec<String>, String> {
+    pub fn set_mime_type(&mut self, path: &str, mime: &str) -> Result<(), FsError> {
+        let metadata = self.metadata_at_mut(path)?;
+        metadata.mime_type = mime.to_string();
+        self.notify(FsEventKind::Metadata, path);
+        Ok(())
+    }
+
+    pub fn search_by_mime_type(&self, mime_type: &str) -> Result<Vec<String>, FsError> {
         let mut results = Vec::new();
-        self.search_by_mime_type_recursive(&self.root, mime_type, &mut results);
+        self.search_by_mime_type_recursive(&self.root, "", mime_type, &mut results);
         Ok(results)
     }
 
-    
     fn search_by_mime_type_recursive(
         &self,
         dir: &Directory,
+        prefix: &str,
         mime_type: &str,
         results: &mut Vec<String>,
     ) {
         for (name, node) in &dir.nodes {
+            let path = format!("{}/{}", prefix, name);
             match node {
                 FSNode::File(file) if file.metadata.mime_type == mime_type => {
-                    results.push(format!("{}/{}", dir.name, file.name));
+                    results.push(path);
                 }
                 FSNode::Directory(subdir) => {
-                    self.search_by_mime_type_recursive(subdir, mime_type, results);
+                    self.search_by_mime_type_recursive(subdir, &path, mime_type, results);
                 }
                 _ => {}
             }
         }
     }
-    pub fn rename(&mut self, old_path: &str, new_name: &str) -> Result<(), String> {
-        let mut parts = old_path
-            .split('/')
-            .filter(|p| !p.is_empty())
-            .collect::<Vec<_>>();
-        if parts.is_empty() {
-            return Err("Invalid path provided.".to_string());
-        }
-
-        let old_name = parts.pop().unwrap();
-        let parent_dir = self.navigate_to_directory(&parts)?;
+    pub fn walk(&self, start: &str) -> Result<Vec<(String, bool)>, FsError> {
+        Ok(self.walk_iter(start)?.collect())
+    }
 
-        if !parent_dir.nodes.contains_key(old_name) {
-            return Err("File or directory not found.".to_string());
-        }
-        if parent_dir.nodes.contains_key(new_name) {
-            return Err("A file or directory with the new name already exists.".to_string());
-        }
+    /// Like [`FileSystem::walk`], but yields the entries one at a time
+    /// instead of collecting them up front.
+    pub fn walk_iter(&self, start: &str) -> Result<Walk<'_>, FsError> {
+        let parts = normalize(start)?;
+        let dir = self.navigate(&parts)?;
+        let prefix = parts.iter().map(|p| format!("/{}", p)).collect::<String>();
+        Ok(Walk {
+            stack: vec![(prefix, dir.nodes.iter())],
+        })
+    }
 
-        let node = parent_dir.nodes.remove(old_name).unwrap();
-        parent_dir.nodes.insert(new_name.to_string(), node);
+    /// Scans the content of every file beneath `start` as raw bytes and
+    /// returns each file containing `needle` together with the byte offset
+    /// of its first occurrence.
+    pub fn grep(&self, needle: &str, start: &str) -> Result<Vec<(String, usize)>, FsError> {
+        let needle = needle.as_bytes();
+        self.grep_with(start, |content| {
+            if needle.is_empty() {
+                return Some(0);
+            }
+            content
+                .windows(needle.len())
+                .position(|window| window == needle)
+        })
+    }
 
-        Ok(())
+    /// Like [`FileSystem::grep`], but matches `pattern` as a regular
+    /// expression over the raw bytes of each file.
+    #[cfg(feature = "regex")]
+    pub fn grep_regex(&self, pattern: &str, start: &str) -> Result<Vec<(String, usize)>, FsError> {
+        let regex = regex::bytes::Regex::new(pattern)
+            .map_err(|e| FsError::InvalidPattern(e.to_string()))?;
+        self.grep_with(start, |content| regex.find(content).map(|m| m.start()))
     }
 
-    pub fn copy(&mut self, source_path: &str, target_path: &str) -> Result<(), String> {
-        let source_parts = source_path
-            .split('/')
-            .filter(|p| !p.is_empty())
-            .collect::<Vec<_>>();
-        let target_parts = target_path
-            .split('/')
-            .filter(|p| !p.is_empty())
-            .collect::<Vec<_>>();
-        let file_name = source_parts
-            .last()
-            .ok_or_else(|| "Invalid source path.".to_string())?;
+    fn grep_with<F>(&self, start: &str, find: F) -> Result<Vec<(String, usize)>, FsError>
+    where
+        F: Fn(&[u8]) -> Option<usize>,
+    {
+        let mut results = Vec::new();
+        for (path, is_dir) in self.walk_iter(start)? {
+            if is_dir {
+                continue;
+            }
+            if let FSNode::File(file) = self.get_node(&path)? {
+                if let Some(offset) = find(&file.content()) {
+                    results.push((path, offset));
+                }
+            }
+        }
+        Ok(results)
+    }
 
-        let (source_dir, _) = self.find_node(source_path)?;
-        let node_to_clone = source_dir
-            .nodes
-            .get(*file_name)
-            .ok_or_else(|| "Source file or directory not found.".to_string())?
-            .clone();
+    /// Returns the files beneath `start` whose `modified_at` is strictly
+    /// after `since`.
+    pub fn modified_since(&self, since: SystemTime, start: &str) -> Result<Vec<String>, FsError> {
+        self.files_where(start, |file| file.metadata.modified_at > since)
+    }
 
-        let target_dir = self.navigate_to_directory(&target_parts)?;
-        target_dir
-            .nodes
-            .insert(file_name.to_string(), node_to_clone);
+    /// Returns the files beneath `start` whose `created_at` is strictly
+    /// after `since`.
+    pub fn created_since(&self, since: SystemTime, start: &str) -> Result<Vec<String>, FsError> {
+        self.files_where(start, |file| file.metadata.created_at > since)
+    }
 
-        Ok(())
+    /// Returns the files beneath `start` whose size lies in `[min, max]`.
+    pub fn find_by_size(
+        &self,
+        min: usize,
+        max: usize,
+        start: &str,
+    ) -> Result<Vec<String>, FsError> {
+        self.files_where(start, |file| (min..=max).contains(&file.content().len()))
     }
 
-    pub fn get_info(&self, path: &str) -> Result<String, String> {
-        let (dir, filename) = self.find_node(path)?;
-        if let Some(node) = dir.nodes.get(filename) {
-            let info = match node {
-                FSNode::File(file) => format!(
-                    "File Name: {}\nSize: {}\nPermissions: {:?}\nOwner: {}\nMIME Type: {}\nTags: {:?}",
-                    file.name, file.metadata.size, file.metadata.permissions, file.metadata.owner, file.metadata.mime_type, file.metadata.tags
-                ),
-                FSNode::Directory(dir) => format!(
-                    "Directory Name: {}\nSize: {}\nPermissions: {:?}\nOwner: {}",
-                    dir.name, dir.metadata.size, dir.metadata.permissions, dir.metadata.owner
-                ),
+    fn files_where<F>(&self, start: &str, predicate: F) -> Result<Vec<String>, FsError>
+    where
+        F: Fn(&File) -> bool,
+    {
+        let mut results = Vec::new();
+        for (path, is_dir) in self.walk_iter(start)? {
+            if is_dir {
+                continue;
+            }
+            if let FSNode::File(file) = self.get_node(&path)? {
+                if predicate(file) {
+                    results.push(path);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    pub fn tree_diff(&self, a: &str, b: &str) -> Result<TreeDiff, FsError> {
+        let entries_a = self.relative_walk(a)?;
+        let entries_b = self.relative_walk(b)?;
+        let a_root = self.resolve(&normalize(a)?, true)?.join("/");
+        let b_root = self.resolve(&normalize(b)?, true)?.join("/");
+
+        let mut diff = TreeDiff::default();
+        for path in &entries_a {
+            if !entries_b.contains(path) {
+                diff.only_in_a.push(path.clone());
+                continue;
+            }
+            let node_a = self.get_node(&format!("{}/{}", a_root, path))?;
+            let node_b = self.get_node(&format!("{}/{}", b_root, path))?;
+            let same = match (node_a, node_b) {
+                (FSNode::File(file_a), FSNode::File(file_b)) => {
+                    *file_a.content() == *file_b.content()
+                }
+                (FSNode::Directory(_), FSNode::Directory(_)) => true,
+                (FSNode::Symlink(link_a), FSNode::Symlink(link_b)) => {
+                    link_a.target == link_b.target
+                }
+                _ => false,
+            };
+            if !same {
+                diff.changed.push(path.clone());
+            }
+        }
+        diff.only_in_b = entries_b
+            .iter()
+            .filter(|path| !entries_a.contains(*path))
+            .cloned()
+            .collect();
+        diff.only_in_a.sort();
+        diff.only_in_b.sort();
+        diff.changed.sort();
+        Ok(diff)
+    }
+
+    /// Makes `target_dir` a mirror of `source_dir`: missing entries are copied
+    /// over, changed ones replaced with the source's version and extra ones
+    /// deleted.
+    pub fn sync(&mut self, source_dir: &str, target_dir: &str) -> Result<SyncReport, FsError> {
+        let diff = self.tree_diff(source_dir, target_dir)?;
+        let source = self.resolve(&normalize(source_dir)?, true)?.join("/");
+        let target = self.resolve(&normalize(target_dir)?, true)?.join("/");
+
+        let mut report = SyncReport::default();
+        let mut handled: Vec<String> = Vec::new();
+        let covered = |handled: &[String], path: &str| {
+            handled.iter().any(|done| {
+                path.strip_prefix(done.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+        };
+        let parent_of = |path: &str| match path.rsplit_once('/') {
+            Some((parent, _)) => format!("{}/{}", target, parent),
+            None => target.clone(),
+        };
+
+        for path in &diff.changed {
+            if !covered(&handled, path) {
+                report.updated.push(path.clone());
+                handled.push(path.clone());
+            }
+        }
+        // Delete before copying so that freed space counts towards quotas.
+        for path in &diff.only_in_b {
+            if !covered(&handled, path) {
+                self.delete_recursive(&format!("{}/{}", target, path))?;
+                report.deleted.push(path.clone());
+                handled.push(path.clone());
+            }
+        }
+        for path in &diff.only_in_a {
+            if !covered(&handled, path) {
+                report.created.push(path.clone());
+                handled.push(path.clone());
+            }
+        }
+        for path in report.updated.iter().chain(&report.created) {
+            self.copy(&format!("{}/{}", source, path), &parent_of(path), true)?;
+        }
+        Ok(report)
+    }
+
+    /// Walks the directory at `start`, keyed by paths relative to it.
+    fn relative_walk(&self, start: &str) -> Result<HashSet<String>, FsError> {
+        let prefix_len = normalize(start)?
+            .iter()
+            .map(|part| part.len() + 1)
+            .sum::<usize>();
+        Ok(self
+            .walk(start)?
+            .into_iter()
+            .map(|(path, _)| path[prefix_len + 1..].to_string())
+            .collect())
+    }
+
+    /// Returns every path beneath `start` whose [`FileStat`] satisfies
+    /// `predicate`, in walk order. Only metadata is inspected, so no access
+    /// times change.
+    pub fn find<F>(&self, start: &str, predicate: F) -> Result<Vec<String>, FsError>
+    where
+        F: Fn(&FileStat) -> bool,
+    {
+        let mut results = Vec::new();
+        for (path, _) in self.walk(start)? {
+            if predicate(&self.stat(&path)?) {
+                results.push(path);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Sums the sizes of the files at or beneath `path`, counting a file
+    /// reachable through several hard links once.
+    pub fn disk_usage(&self, path: &str) -> Result<usize, FsError> {
+        if self.is_file(path) {
+            return Ok(self.get_file(path)?.content().len());
+        }
+        let mut seen = HashSet::new();
+        let mut total = 0;
+        for (entry, is_dir) in self.walk(path)? {
+            if is_dir {
+                continue;
+            }
+            if let FSNode::File(file) = self.get_node(&entry)? {
+                if seen.insert(Arc::as_ptr(&file.content) as usize) {
+                    total += file.content().len();
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// Renders the subtree as an indented text tree. Directories are listed
+    /// before files, each group sorted by name. Files are followed by their
+    /// size and nodes flagged hidden are suffixed with `(hidden)`.
+    pub fn tree(&self, start: &str) -> Result<String, FsError> {
+        let parts = normalize(start)?;
+        let dir = self.navigate(&parts)?;
+        let mut out = format!("/{}\n", parts.join("/"));
+        self.tree_recursive(dir, "", &mut out);
+        Ok(out)
+    }
+
+    fn tree_recursive(&self, dir: &Directory, indent: &str, out: &mut String) {
+        let mut entries = dir.nodes.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a_name, a), (b_name, b)| {
+            let a_is_file = !matches!(a, FSNode::Directory(_));
+            let b_is_file = !matches!(b, FSNode::Directory(_));
+            (a_is_file, a_name).cmp(&(b_is_file, b_name))
+        });
+        for (i, (name, node)) in entries.iter().enumerate() {
+            let last = i == entries.len() - 1;
+            out.push_str(indent);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(name);
+            match node {
+                FSNode::Directory(_) => out.push('/'),
+                FSNode::Symlink(link) => {
+                    out.push_str(" -> ");
+                    out.push_str(&link.target);
+                }
+                FSNode::File(file) => {
+                    out.push_str(" (");
+                    out.push_str(&human_size(file.content().len()));
+                    out.push(')');
+                }
+            }
+            if node.metadata_ref().is_hidden {
+                out.push_str(" (hidden)");
+            }
+            out.push('\n');
+            if let FSNode::Directory(subdir) = node {
+                let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+                self.tree_recursive(subdir, &child_indent, out);
+            }
+        }
+    }
+
+    /// Renders the subtree at `start` as a Graphviz digraph. Nodes are keyed
+    /// by their full path and labelled with their name; directories are
+    /// drawn as folders, files as notes and symbolic links as dashed boxes.
+    pub fn to_dot(&self, start: &str) -> Result<String, FsError> {
+        let parts = normalize(start)?;
+        let dir = self.navigate(&parts)?;
+        let path = format!("/{}", parts.join("/"));
+        let mut out = String::from("digraph filesystem {\n");
+        out.push_str(&format!(
+            "    \"{}\" [label=\"{}\", shape=folder];\n",
+            dot_escape(&path),
+            dot_escape(parts.last().map_or("/", String::as_str))
+        ));
+        self.dot_recursive(
+            dir,
+            &parts.iter().map(|p| format!("/{}", p)).collect::<String>(),
+            &mut out,
+        );
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    fn dot_recursive(&self, dir: &Directory, prefix: &str, out: &mut String) {
+        let parent = if prefix.is_empty() { "/" } else { prefix };
+        for (name, node) in &dir.nodes {
+            let path = format!("{}/{}", prefix, name);
+            let attributes = match node {
+                FSNode::Directory(_) => "shape=folder",
+                FSNode::File(_) => "shape=note",
+                FSNode::Symlink(_) => "shape=box, style=dashed",
             };
-            Ok(info)
+            out.push_str(&format!(
+                "    \"{}\" [label=\"{}\", {}];\n",
+                dot_escape(&path),
+                dot_escape(name),
+                attributes
+            ));
+            out.push_str(&format!(
+                "    \"{}\" -> \"{}\";\n",
+                dot_escape(parent),
+                dot_escape(&path)
+            ));
+            if let FSNode::Directory(subdir) = node {
+                self.dot_recursive(subdir, &path, out);
+            }
+        }
+    }
+
+    pub fn glob(&self, pattern: &str) -> Result<Vec<String>, FsError> {
+        let parts = normalize(pattern)?;
+        if parts.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        let mut results = Vec::new();
+        self.glob_recursive(&self.root, "", &parts, &mut results);
+        results.sort();
+        results.dedup();
+        Ok(results)
+    }
+
+    fn glob_recursive(
+        &self,
+        dir: &Directory,
+        prefix: &str,
+        parts: &[String],
+        results: &mut Vec<String>,
+    ) {
+        let Some((first, rest)) = parts.split_first() else {
+            return;
+        };
+        if *first == "**" {
+            self.glob_recursive(dir, prefix, rest, results);
+            for (name, node) in &dir.nodes {
+                let path = format!("{}/{}", prefix, name);
+                if rest.is_empty() {
+                    results.push(path.clone());
+                }
+                if let FSNode::Directory(subdir) = node {
+                    self.glob_recursive(subdir, &path, parts, results);
+                }
+            }
+        } else {
+            for (name, node) in &dir.nodes {
+                if !wildcard_match(first, name, self.case_insensitive) {
+                    continue;
+                }
+                let path = format!("{}/{}", prefix, name);
+                if rest.is_empty() {
+                    results.push(path);
+                } else if let FSNode::Directory(subdir) = node {
+                    self.glob_recursive(subdir, &path, rest, results);
+                }
+            }
+        }
+    }
+
+    /// Renames the node at `old_path` to `new_name` within its directory.
+    /// A `new_name` containing `/` is taken as a full destination path
+    /// instead, and the node is moved there like with `move_node`.
+    pub fn rename(&mut self, old_path: &str, new_name: &str) -> Result<(), FsError> {
+        if new_name.contains('/') {
+            return self.move_node(old_path, new_name);
+        }
+        self.validate_name(new_name)?;
+        let mut parts = normalize(old_path)?;
+        if parts.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        self.check_unmounted(old_path)?;
+
+        let old_name = parts.pop().unwrap();
+        let fold_case = self.case_insensitive;
+        let parent_dir = self.navigate_to_directory(&parts)?;
+        let old_name = parent_dir.entry_name(&old_name, fold_case);
+
+        match parent_dir.nodes.get(&old_name) {
+            None => return Err(FsError::NotFound),
+            Some(node) if node.metadata_ref().is_read_only => return Err(FsError::ReadOnly),
+            Some(_) => {}
+        }
+        // Renaming to a different case of the same name is fine.
+        if parent_dir.entry_name(new_name, fold_case) != old_name
+            && parent_dir.contains(new_name, fold_case)
+        {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let mut node = parent_dir.nodes.remove(&old_name).unwrap();
+        node.set_name(new_name);
+        parent_dir.nodes.insert(new_name.to_string(), node);
+        let to = join(&parts.join("/"), new_name);
+        self.notify(FsEventKind::Rename { to }, old_path);
+
+        Ok(())
+    }
+
+    pub fn move_node(&mut self, source_path: &str, target_path: &str) -> Result<(), FsError> {
+        let source_parts = self.resolve(&normalize(source_path)?, false)?;
+        let target_parts = self.resolve(&normalize(target_path)?, false)?;
+        if source_parts.is_empty() || target_parts.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        self.check_unmounted(source_path)?;
+        // A directory can't be moved into one of its descendants.
+        if target_parts.starts_with(&source_parts) && target_parts.len() > source_parts.len() {
+            return Err(FsError::InvalidPath);
+        }
+
+        let (source_name, source_parts) = source_parts.split_last().unwrap();
+        let (_, target_parts) = target_parts.split_last().unwrap();
+        // Resolving takes on the case of an existing entry; the new name
+        // keeps the case it was given.
+        let target_name = basename(target_path).ok_or(FsError::InvalidPath)?;
+        self.validate_name(&target_name)?;
+
+        match self.navigate(source_parts)?.nodes.get(source_name) {
+            None => return Err(FsError::NotFound),
+            Some(node) if node.metadata_ref().is_read_only => return Err(FsError::ReadOnly),
+            Some(_) => {}
+        }
+        // Moving to a different case of the same name is fine.
+        let target_dir = self.navigate(target_parts)?;
+        if (target_parts != source_parts
+            || target_dir.entry_name(&target_name, self.case_insensitive) != *source_name)
+            && target_dir.contains(&target_name, self.case_insensitive)
+        {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let height = self.navigate(source_parts)?.nodes[source_name].height();
+        self.check_limits(target_parts, height, target_parts != source_parts, 0)?;
+
+        let mut node = self
+            .navigate_to_directory(source_parts)?
+            .nodes
+            .remove(source_name)
+            .unwrap();
+        node.set_name(&target_name);
+        self.navigate_to_directory(target_parts)?
+            .nodes
+            .insert(target_name, node);
+        let to = target_path.to_string();
+        self.notify(FsEventKind::Rename { to }, source_path);
+
+        Ok(())
+    }
+
+    /// Deep-copies the node at `source_path` into the directory `target_path`,
+    /// keeping its name. The copy keeps the source's metadata except for a
+    /// fresh `created_at` on every copied node. An existing entry with the
+    /// same name is only replaced when `overwrite` is set.
+    pub fn copy(
+        &mut self,
+        source_path: &str,
+        target_path: &str,
+        overwrite: bool,
+    ) -> Result<(), FsError> {
+        self.copy_node(source_path, target_path, overwrite, false, Refresh::Created)
+    }
+
+    /// Like [`FileSystem::copy`], with the timestamps chosen by `options`:
+    /// either every copied node keeps the source's `created_at`,
+    /// `modified_at` and `accessed_at`, or it gets the current time for all
+    /// three.
+    pub fn copy_with_options(
+        &mut self,
+        source_path: &str,
+        target_path: &str,
+        options: CopyOptions,
+    ) -> Result<(), FsError> {
+        let refresh = if options.preserve_timestamps {
+            Refresh::None
         } else {
-            Err("File or directory not found.".to_string())
+            Refresh::All
+        };
+        self.copy_node(source_path, target_path, options.overwrite, false, refresh)
+    }
+
+    /// Like [`FileSystem::copy`] without overwriting, but copies one node at
+    /// a time and calls `on_progress(done, total)` after each. Returning
+    /// `false` from the callback cancels the copy and undoes every node
+    /// copied so far; so does any error along the way.
+    pub fn copy_with_progress<F>(
+        &mut self,
+        source: &str,
+        target: &str,
+        mut on_progress: F,
+    ) -> Result<(), FsError>
+    where
+        F: FnMut(u64, u64) -> bool,
+    {
+        let source_parts = normalize(source)?;
+        let name = source_parts.last().ok_or(FsError::InvalidPath)?;
+        let prefix = source_parts
+            .iter()
+            .map(|p| format!("/{}", p))
+            .collect::<String>();
+        let destination = normalize(target)?
+            .iter()
+            .map(|p| format!("/{}", p))
+            .chain([format!("/{}", name)])
+            .collect::<String>();
+
+        let mut paths = vec![prefix.clone()];
+        if let FSNode::Directory(_) = self.get_node(source)? {
+            paths.extend(self.walk(source)?.into_iter().map(|(path, _)| path));
+        }
+
+        let saved = self.snapshot();
+        let total = paths.len() as u64;
+        for (done, path) in paths.iter().enumerate() {
+            let copied = format!("{}{}", destination, &path[prefix.len()..]);
+            let (parent, _) = copied.rsplit_once('/').unwrap();
+            let result = self
+                .copy_node(path, parent, false, true, Refresh::Created)
+                .and_then(|()| {
+                    if on_progress(done as u64 + 1, total) {
+                        Ok(())
+                    } else {
+                        Err(FsError::Cancelled)
+                    }
+                });
+            if let Err(err) = result {
+                self.roll_back(saved);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the node at `source_path` into `target_path`. With `shallow`
+    /// set, a directory is copied without its children.
+    fn copy_node(
+        &mut self,
+        source_path: &str,
+        target_path: &str,
+        overwrite: bool,
+        shallow: bool,
+        refresh: Refresh,
+    ) -> Result<(), FsError> {
+        let now = self.now();
+        let target_parts = normalize(target_path)?;
+        // A directory can't be copied into itself or one of its descendants.
+        let source_parts = self.resolve(&normalize(source_path)?, false)?;
+        if self
+            .resolve(&target_parts, true)?
+            .starts_with(&source_parts)
+        {
+            return Err(FsError::InvalidPath);
+        }
+        let (source_dir, file_name) = self.find_node(source_path)?;
+        let file_name = &file_name;
+        let mut node_to_clone = source_dir
+            .nodes
+            .get(file_name)
+            .ok_or(FsError::NotFound)?
+            .clone();
+        if let (true, FSNode::Directory(dir)) = (shallow, &mut node_to_clone) {
+            dir.nodes.clear();
+        }
+
+        let existing = self
+            .navigate(&target_parts)?
+            .entry_name(file_name, self.case_insensitive);
+        if !overwrite && self.navigate(&target_parts)?.nodes.contains_key(&existing) {
+            return Err(FsError::AlreadyExists);
+        }
+        self.check_unmounted(&join(target_path, &existing))?;
+        let replaced = self
+            .navigate(&target_parts)?
+            .nodes
+            .get(&existing)
+            .map_or(0, |node| node.content_bytes(&mut HashSet::new()));
+        let resolved = self.resolve(&target_parts, true)?;
+        let replaced_nodes = self
+            .navigate(&target_parts)?
+            .nodes
+            .get(&existing)
+            .map_or(0, FSNode::node_count);
+        self.check_limits(
+            &resolved,
+            node_to_clone.height(),
+            replaced_nodes == 0,
+            node_to_clone.node_count().saturating_sub(replaced_nodes),
+        )?;
+        let room = self.room_in(&resolved, replaced);
+        check_room(
+            room,
+            replaced,
+            node_to_clone.content_bytes(&mut HashSet::new()),
+        )?;
+
+        node_to_clone.refresh_times(refresh, now);
+        self.reassign_inodes(&mut node_to_clone);
+        let target_dir = self.navigate_to_directory(&target_parts)?;
+        target_dir.nodes.remove(&existing);
+        target_dir
+            .nodes
+            .insert(file_name.to_string(), node_to_clone);
+        self.notify(FsEventKind::Create, &join(target_path, file_name));
+
+        Ok(())
+    }
+
+    /// Returns the inode of the node at `path`. A symbolic link has an inode
+    /// of its own, distinct from its target's.
+    pub fn inode(&self, path: &str) -> Result<u64, FsError> {
+        Ok(self.metadata_at(path)?.inode)
+    }
+
+    /// Finds a path for the node with inode `id`. Hard links share an inode,
+    /// in which case the first of their paths in walk order is returned.
+    pub fn path_of_inode(&self, id: u64) -> Option<String> {
+        if self.root.metadata.inode == id {
+            return Some("/".to_string());
+        }
+        self.walk("/")
+            .ok()?
+            .into_iter()
+            .map(|(path, _)| path)
+            .find(|path| {
+                self.get_node(path)
+                    .is_ok_and(|node| node.metadata_ref().inode == id)
+            })
+    }
+
+    /// Returns the absolute path of the node at `path` with every symbolic
+    /// link followed. Unlike plain string normalization, `..` is applied
+    /// after links are resolved, so `/link/..` is the parent of the link's
+    /// target. Fails with `NotFound` when any component is missing.
+    pub fn canonicalize(&self, path: &str) -> Result<String, FsError> {
+        let parts = path.split('/').map(str::to_string).collect::<Vec<_>>();
+        let parts = self.resolve(&parts, true)?;
+        let path = format!("/{}", parts.join("/"));
+        self.metadata_at(&path)?;
+        Ok(path)
+    }
+
+    /// Returns the relative path that leads from `from` to `to`, such as
+    /// `../b/c.txt`, or `.` when both are the same. A `from` that is a
+    /// directory is the starting point itself; for anything else its parent
+    /// directory is, as for the target of a relative symbolic link. Both
+    /// nodes must exist, and symbolic links along either path are followed.
+    pub fn relative_path(&self, from: &str, to: &str) -> Result<String, FsError> {
+        let mut base = self.resolve(&normalize(from)?, true)?;
+        let target = self.resolve(&normalize(to)?, true)?;
+        self.metadata_at(&target.join("/"))?;
+        if self.navigate_physical(&base).is_err() {
+            self.metadata_at(&base.join("/"))?;
+            base.pop();
+        }
+        let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+        let parts = std::iter::repeat_n("..", base.len() - common)
+            .chain(target[common..].iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            return Ok(".".to_string());
+        }
+        Ok(parts.join("/"))
+    }
+
+    /// Copies every entry of `source_dir` into `target_dir`. Directories that
+    /// exist on both sides are merged recursively; any other name collision
+    /// keeps the target's entry unless `overwrite` is set.
+    pub fn merge(
+        &mut self,
+        source_dir: &str,
+        target_dir: &str,
+        overwrite: bool,
+    ) -> Result<(), FsError> {
+        let source = self.resolve(&normalize(source_dir)?, true)?.join("/");
+        let target = self.resolve(&normalize(target_dir)?, true)?.join("/");
+        if !self.is_directory(&source) || !self.is_directory(&target) {
+            return Err(FsError::NotADirectory);
+        }
+        for name in self.list_directory(&source)? {
+            let source_path = format!("{}/{}", source, name);
+            let target_path = format!("{}/{}", target, name);
+            let both_dirs = matches!(self.get_node(&source_path)?, FSNode::Directory(_))
+                && matches!(self.get_node(&target_path), Ok(FSNode::Directory(_)));
+            if both_dirs {
+                self.merge(&source_path, &target_path, overwrite)?;
+            } else if !self.exists(&target_path) || overwrite {
+                self.copy(&source_path, &target, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `f` and rolls the whole tree, the trash and the mounts back if
+    /// it returns an error. The rollback point is a full copy taken up
+    /// front, so the cost grows with the size of the filesystem, not of the
+    /// change.
+    pub fn transaction<F>(&mut self, f: F) -> Result<(), FsError>
+    where
+        F: FnOnce(&mut FileSystem) -> Result<(), FsError>,
+    {
+        let saved = self.snapshot();
+        let result = f(self);
+        if result.is_err() {
+            self.roll_back(saved);
         }
+        result
+    }
+
+    /// Creates every file in `files`, along with any missing parent
+    /// directories. Either all files are created or, on the first error,
+    /// none are and the tree is left as it was.
+    pub fn create_many(&mut self, files: HashMap<String, Vec<u8>>) -> Result<(), FsError> {
+        let mut files = files.into_iter().collect::<Vec<_>>();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.transaction(|fs| {
+            for (path, content) in files {
+                fs.create_dir_all(&parent(&path).ok_or(FsError::InvalidPath)?)?;
+                fs.create(&path, Some(content), false)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Captures the current tree, trash and mounts so they can later be
+    /// brought back with [`FileSystem::restore`]. Hard links inside the
+    /// snapshot stay linked, including links between the tree and the
+    /// trash.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut copies = HashMap::new();
+        let root = self.root.clone_with_links(&mut copies);
+        let trash = self
+            .trash
+            .iter()
+            .map(|(path, node)| (path.clone(), node.clone_with_links(&mut copies)))
+            .collect();
+        Snapshot {
+            root,
+            trash,
+            mounts: self.mounts.clone(),
+        }
+    }
+
+    /// Puts back the tree, trash and mounts captured by `snapshot`. Fails
+    /// with `PermissionDenied` while the filesystem is frozen.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), FsError> {
+        self.check_thawed()?;
+        self.roll_back(snapshot);
+        Ok(())
+    }
+
+    /// Like `restore`, for undoing a failed operation: nothing could change
+    /// while frozen, so there is nothing to guard.
+    fn roll_back(&mut self, snapshot: Snapshot) {
+        self.root = snapshot.root;
+        self.trash = snapshot.trash;
+        self.mounts = snapshot.mounts;
+        self.rebuild_indexes();
+    }
+
+    pub fn metadata(&self, path: &str) -> Result<MetadataView, FsError> {
+        let stat = self.stat(path)?;
+        let metadata = self.metadata_at(path)?;
+        Ok(MetadataView {
+            owner: metadata.owner.clone(),
+            group: metadata.group.clone(),
+            size: stat.size,
+            permissions: metadata.permissions,
+            created_at: metadata.created_at,
+            modified_at: metadata.modified_at,
+            accessed_at: metadata.accessed_at,
+            mime_type: metadata.mime_type.clone(),
+            tags: metadata.tags.clone(),
+        })
+    }
+
+    /// Describes the node at `path`. The root is reported as a directory
+    /// named `/`.
+    pub fn stat(&self, path: &str) -> Result<FileStat, FsError> {
+        let metadata = self.metadata_at(path)?;
+        let (name, size, is_dir, is_symlink) = if normalize(path)?.is_empty() {
+            (self.root.name.clone(), metadata.size, true, false)
+        } else {
+            let node = self.get_node(path)?;
+            (
+                node.name().to_string(),
+                node.size(),
+                matches!(node, FSNode::Directory(_)),
+                matches!(node, FSNode::Symlink(_)),
+            )
+        };
+        Ok(FileStat {
+            name,
+            size,
+            is_dir,
+            is_symlink,
+            permissions: metadata.permissions,
+            owner: metadata.owner.clone(),
+            group: metadata.group.clone(),
+            mime_type: metadata.mime_type.clone(),
+            tags: metadata.tags.clone(),
+            created_at: metadata.created_at,
+            modified_at: metadata.modified_at,
+            accessed_at: metadata.accessed_at,
+        })
+    }
+
+    pub fn get_info(&self, path: &str) -> Result<String, FsError> {
+        Ok(self.stat(path)?.to_string())
+    }
+
+    /// Like `==`, but timestamps must match too.
+    pub fn eq_including_times(&self, other: &FileSystem) -> bool {
+        self.root.same_tree(&other.root, true)
     }
 }
 impl Metadata {
-    fn default() -> Self {
-        let now = SystemTime::now();
+    fn new(inode: u64, now: SystemTime) -> Self {
         Metadata {
+            inode,
             created_at: now,
             modified_at: now,
             accessed_at: now,
@@ -387,20 +2694,1884 @@ impl Metadata {
                 write: true,
                 execute: false,
             },
+            group_permissions: Permissions::new(true, false, false),
+            other_permissions: Permissions::new(true, false, false),
             owner: "root".to_string(),
             group: "root".to_string(),
             is_read_only: false,
             is_hidden: false,
             mime_type: "text/plain".to_string(),
             tags: vec![],
+            xattrs: HashMap::new(),
+            acl: Vec::new(),
         }
     }
 }
 impl FSNode {
+    fn content_bytes(&self, seen: &mut HashSet<usize>) -> usize {
+        match self {
+            FSNode::File(file) if seen.insert(Arc::as_ptr(&file.content) as usize) => {
+                file.raw_content().data.len()
+            }
+            FSNode::Directory(dir) => dir.content_bytes(seen),
+            _ => 0,
+        }
+    }
+
+    /// Memory held by this node beyond its slot in the parent's table.
+    fn memory_usage(&self, seen: &mut HashSet<usize>) -> usize {
+        match self {
+            FSNode::File(file) => {
+                let mut bytes = file.name.capacity() + file.metadata.memory_usage();
+                if seen.insert(Arc::as_ptr(&file.content) as usize) {
+                    let content = file.raw_content();
+                    bytes += std::mem::size_of::<RwLock<Content>>();
+                    if seen.insert(Arc::as_ptr(&content.data) as usize) {
+                        bytes += content.data.capacity();
+                    }
+                    bytes += content.history.iter().map(Vec::capacity).sum::<usize>();
+                }
+                bytes
+            }
+            FSNode::Directory(dir) => dir.memory_usage(seen),
+            FSNode::Symlink(link) => {
+                link.name.capacity() + link.target.capacity() + link.metadata.memory_usage()
+            }
+        }
+    }
+
+    fn same_tree(&self, other: &FSNode, times: bool) -> bool {
+        match (self, other) {
+            (FSNode::File(a), FSNode::File(b)) => {
+                a.metadata.same_as(&b.metadata, times) && *a.content() == *b.content()
+            }
+            (FSNode::Directory(a), FSNode::Directory(b)) => a.same_tree(b, times),
+            (FSNode::Symlink(a), FSNode::Symlink(b)) => {
+                a.metadata.same_as(&b.metadata, times) && a.target == b.target
+            }
+            _ => false,
+        }
+    }
+
+    fn for_each_file_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut File),
+    {
+        match self {
+            FSNode::File(file) => f(file),
+            FSNode::Directory(dir) => {
+                for node in dir.nodes.values_mut() {
+                    node.for_each_file_mut(f);
+                }
+            }
+            FSNode::Symlink(_) => {}
+        }
+    }
+
+    /// The number of nodes in this subtree, counting this node.
+    fn node_count(&self) -> usize {
+        match self {
+            FSNode::Directory(dir) => 1 + dir.node_count(),
+            _ => 1,
+        }
+    }
+
+    /// The number of levels in this subtree; 1 for anything but a
+    /// non-empty directory.
+    fn height(&self) -> usize {
+        match self {
+            FSNode::Directory(dir) => 1 + dir.nodes.values().map(FSNode::height).max().unwrap_or(0),
+            _ => 1,
+        }
+    }
+
     fn metadata(&mut self) -> &mut Metadata {
         match self {
             FSNode::File(file) => &mut file.metadata,
             FSNode::Directory(dir) => &mut dir.metadata,
+            FSNode::Symlink(link) => &mut link.metadata,
+        }
+    }
+
+    fn refresh_times(&mut self, refresh: Refresh, now: SystemTime) {
+        let metadata = self.metadata();
+        match refresh {
+            Refresh::None => return,
+            Refresh::Created => metadata.created_at = now,
+            Refresh::All => {
+                metadata.created_at = now;
+                metadata.modified_at = now;
+                metadata.accessed_at = now;
+            }
+        }
+        if let FSNode::Directory(dir) = self {
+            for node in dir.nodes.values_mut() {
+                node.refresh_times(refresh, now);
+            }
+        }
+    }
+
+    /// Deep-copies the node. Files whose content is already in `copies`
+    /// get linked to that copy instead of a new one.
+    fn clone_with_links(&self, copies: &mut HashMap<usize, Arc<RwLock<Content>>>) -> FSNode {
+        match self {
+            FSNode::File(file) => {
+                let content = copies
+                    .entry(Arc::as_ptr(&file.content) as usize)
+                    .or_insert_with(|| Arc::new(RwLock::new(file.raw_content().clone())))
+                    .clone();
+                FSNode::File(File {
+                    name: file.name.clone(),
+                    content,
+                    metadata: file.metadata.clone(),
+                })
+            }
+            FSNode::Directory(dir) => FSNode::Directory(dir.clone_with_links(copies)),
+            FSNode::Symlink(link) => FSNode::Symlink(link.clone()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            FSNode::File(file) => file.content().len(),
+            _ => self.metadata_ref().size,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            FSNode::File(file) => &file.name,
+            FSNode::Directory(dir) => &dir.name,
+            FSNode::Symlink(link) => &link.name,
+        }
+    }
+
+    fn set_name(&mut self, name: &str) {
+        match self {
+            FSNode::File(file) => file.name = name.to_string(),
+            FSNode::Directory(dir) => dir.name = name.to_string(),
+            FSNode::Symlink(link) => link.name = name.to_string(),
+        }
+    }
+
+    fn metadata_ref(&self) -> &Metadata {
+        match self {
+            FSNode::File(file) => &file.metadata,
+            FSNode::Directory(dir) => &dir.metadata,
+            FSNode::Symlink(link) => &link.metadata,
+        }
+    }
+}
+
+impl Directory {
+    /// Returns the key an entry called `name` is stored under. With
+    /// `fold_case` set, an entry whose name differs only in case matches;
+    /// names with no entry come back unchanged.
+    fn entry_name(&self, name: &str, fold_case: bool) -> String {
+        if fold_case && !self.nodes.contains_key(name) {
+            let folded = name.to_lowercase();
+            if let Some(key) = self.nodes.keys().find(|key| key.to_lowercase() == folded) {
+                return key.clone();
+            }
         }
+        name.to_string()
+    }
+
+    fn contains(&self, name: &str, fold_case: bool) -> bool {
+        self.nodes.contains_key(&self.entry_name(name, fold_case))
+    }
+
+    /// Sums the content bytes below this directory, counting each buffer
+    /// shared by hard links only once.
+    fn content_bytes(&self, seen: &mut HashSet<usize>) -> usize {
+        self.nodes
+            .values()
+            .map(|node| node.content_bytes(seen))
+            .sum()
+    }
+
+    fn node_count(&self) -> usize {
+        self.nodes.values().map(FSNode::node_count).sum()
+    }
+
+    fn memory_usage(&self, seen: &mut HashSet<usize>) -> usize {
+        let slot = std::mem::size_of::<String>() + std::mem::size_of::<FSNode>();
+        let entries = self
+            .nodes
+            .iter()
+            .map(|(name, node)| name.capacity() + node.memory_usage(seen))
+            .sum::<usize>();
+        self.name.capacity() + self.metadata.memory_usage() + self.nodes.len() * slot + entries
+    }
+
+    fn collect_stats(&self, depth: usize, stats: &mut FsStats) {
+        for node in self.nodes.values() {
+            stats.max_depth = stats.max_depth.max(depth);
+            match node {
+                FSNode::File(_) => {
+                    stats.file_count += 1;
+                    stats.total_bytes += node.size();
+                }
+                FSNode::Directory(dir) => {
+                    stats.dir_count += 1;
+                    dir.collect_stats(depth + 1, stats);
+                }
+                FSNode::Symlink(_) => stats.symlink_count += 1,
+            }
+        }
+    }
+
+    fn same_tree(&self, other: &Directory, times: bool) -> bool {
+        self.quota == other.quota
+            && self.metadata.same_as(&other.metadata, times)
+            && self.nodes.len() == other.nodes.len()
+            && self.nodes.iter().all(|(name, node)| {
+                other
+                    .nodes
+                    .get(name)
+                    .is_some_and(|other| node.same_tree(other, times))
+            })
+    }
+
+    /// Deep-copies the subtree like `clone`, except that files which were
+    /// hard links to each other stay linked to each other in the copy.
+    fn clone_with_links(&self, copies: &mut HashMap<usize, Arc<RwLock<Content>>>) -> Directory {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(name, node)| (name.clone(), node.clone_with_links(copies)))
+            .collect();
+        Directory {
+            name: self.name.clone(),
+            nodes,
+            metadata: self.metadata.clone(),
+            quota: self.quota,
+        }
+    }
+}
+
+/// Formats a byte count with binary units: `512 B`, `1.5 KiB`, `2.0 MiB`.
+pub fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // Step up before rounding would print e.g. "1024.0 KiB".
+    while value >= 1024.0 - 0.05 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+pub fn guess_mime_from_extension(path: &str) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => extension.to_ascii_lowercase(),
+        _ => return "application/octet-stream",
+    };
+    match extension.as_str() {
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+fn check_room(room: Option<usize>, old_len: usize, new_len: usize) -> Result<(), FsError> {
+    if new_len > old_len && room.is_some_and(|room| new_len > room) {
+        return Err(FsError::QuotaExceeded);
+    }
+    Ok(())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Matches `name` against a pattern of `*` and `?` wildcards, ignoring case
+/// when `fold_case` is set.
+fn wildcard_match(pattern: &str, name: &str, fold_case: bool) -> bool {
+    let fold = |s: &str| -> Vec<char> {
+        if fold_case {
+            s.to_lowercase().chars().collect()
+        } else {
+            s.chars().collect()
+        }
+    };
+    let pattern = fold(pattern);
+    let name = fold(name);
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn parse_mode(mode: &str) -> Result<[Permissions; 3], FsError> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    let digits = if digits.len() == 4 {
+        digits.strip_prefix('0').unwrap_or(digits)
+    } else {
+        digits
+    };
+    if digits.len() == 3 && digits.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        let mut bits = digits
+            .bytes()
+            .map(|b| Permissions::from_bits(u32::from(b - b'0')));
+        return Ok([
+            bits.next().unwrap(),
+            bits.next().unwrap(),
+            bits.next().unwrap(),
+        ]);
+    }
+    if mode.len() == 9 && mode.is_ascii() {
+        let triple =
+            |i: usize| Permissions::from_symbolic(&mode[i..i + 3]).ok_or(FsError::InvalidMode);
+        return Ok([triple(0)?, triple(3)?, triple(6)?]);
+    }
+    Err(FsError::InvalidMode)
+}
+
+/// Escapes `s` for use inside a double-quoted DOT identifier.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns the normalized path of the directory containing `path`, or
+/// `None` for the root and for paths that climb above it.
+pub fn parent(path: &str) -> Option<String> {
+    let mut parts = normalize(path).ok()?;
+    parts.pop()?;
+    Some(format!("/{}", parts.join("/")))
+}
+
+/// Returns the last component of the normalized `path`, or `None` for the
+/// root and for paths that climb above it.
+pub fn basename(path: &str) -> Option<String> {
+    normalize(path).ok()?.pop()
+}
+
+/// Appends `child` to `base` and normalizes the result. `child` is always
+/// taken as relative to `base`, and `..` never climbs above the root.
+pub fn join(base: &str, child: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in base.split('/').chain(child.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn normalize(path: &str) -> Result<Vec<String>, FsError> {
+    let mut parts: Vec<String> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or(FsError::InvalidPath)?;
+            }
+            _ => parts.push(part.to_string()),
+        }
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A clock that only moves when told to. Clones share the same time.
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeClock(Arc<AtomicU64>);
+
+    impl FakeClock {
+        pub(crate) fn advance(&self, secs: u64) {
+            self.0.fetch_add(secs, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_secs(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn write_file_persists() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", None, false).unwrap();
+        fs.write_file("/a.txt", b"hello".to_vec(), false).unwrap();
+        assert_eq!(fs.read_file("/a.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn permission_changes_persist() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", Some(b"x".to_vec()), false).unwrap();
+        fs.change_permissions("/a.txt", Permissions::new(true, false, true))
+            .unwrap();
+        assert_eq!(
+            fs.metadata("/a.txt").unwrap().permissions,
+            Permissions::new(true, false, true)
+        );
+    }
+
+    #[test]
+    fn appends_persist() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", Some(b"ab".to_vec()), false).unwrap();
+        fs.write_file("/a.txt", b"cd".to_vec(), true).unwrap();
+        fs.update_file("/a.txt", b"ef".to_vec(), true).unwrap();
+        assert_eq!(fs.read_file("/a.txt").unwrap(), b"abcdef");
+    }
+
+    #[test]
+    fn delete_recursive_removes_subtree() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/c.txt", Some(b"c".to_vec()), false).unwrap();
+        assert_eq!(fs.delete("/a"), Err(FsError::DirectoryNotEmpty));
+        fs.delete_recursive("/a").unwrap();
+        assert!(!fs.exists("/a"));
+        assert!(!fs.exists("/a/b/c.txt"));
+        assert_eq!(fs.list_directory("/").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn move_node_between_directories() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a").unwrap();
+        fs.create_dir_all("/b").unwrap();
+        fs.create("/a/x.txt", Some(b"x".to_vec()), false).unwrap();
+        fs.move_node("/a/x.txt", "/b/x.txt").unwrap();
+        assert!(!fs.exists("/a/x.txt"));
+        assert_eq!(fs.read_file("/b/x.txt").unwrap(), b"x");
+    }
+
+    #[test]
+    fn move_node_into_own_descendant_fails() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        assert_eq!(fs.move_node("/a", "/a/b/a"), Err(FsError::InvalidPath));
+        assert!(fs.is_directory("/a/b"));
+    }
+
+    #[test]
+    fn metadata_view_reports_node_metadata() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", Some(b"abc".to_vec()), false).unwrap();
+        let permissions = Permissions::new(true, false, false);
+        assert!(permissions.read && !permissions.write && !permissions.execute);
+        fs.change_permissions("/a.txt", permissions).unwrap();
+        let metadata = fs.metadata("/a.txt").unwrap();
+        assert_eq!(metadata.owner, "root");
+        assert_eq!(metadata.size, 3);
+        assert_eq!(metadata.permissions, permissions);
+        assert_eq!(metadata.mime_type, "text/plain");
+        assert_eq!(fs.metadata("/missing").unwrap_err(), FsError::NotFound);
+    }
+
+    #[test]
+    fn errors_carry_variant_and_message() {
+        let mut fs = FileSystem::new();
+        assert_eq!(fs.read_file("/missing"), Err(FsError::NotFound));
+        fs.create("/a", None, true).unwrap();
+        assert_eq!(fs.create("/a", None, true), Err(FsError::AlreadyExists));
+        assert_eq!(fs.read_file("/a"), Err(FsError::NotAFile));
+        assert_eq!(
+            FsError::NotFound.to_string(),
+            "File or directory not found."
+        );
+        assert_eq!(
+            FsError::Serialization("bad".to_string()).to_string(),
+            "Serialization failed: bad"
+        );
+        let err: Box<dyn std::error::Error> = Box::new(FsError::ReadOnly);
+        assert_eq!(err.to_string(), "File or directory is read-only.");
+    }
+
+    #[test]
+    fn exists_and_node_type_queries() {
+        let mut fs = FileSystem::new();
+        fs.create("/dir", None, true).unwrap();
+        fs.create("/dir/file.txt", None, false).unwrap();
+        assert!(!fs.exists("/missing"));
+        assert!(!fs.is_file("/missing") && !fs.is_directory("/missing"));
+        assert!(fs.exists("/dir/file.txt"));
+        assert!(fs.is_file("/dir/file.txt") && !fs.is_directory("/dir/file.txt"));
+        assert!(fs.exists("/dir"));
+        assert!(fs.is_directory("/dir") && !fs.is_file("/dir"));
+    }
+
+    #[test]
+    fn create_dir_all_creates_missing_parents() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/x/y/z").unwrap();
+        assert!(fs.is_directory("/x"));
+        assert!(fs.is_directory("/x/y"));
+        assert!(fs.is_directory("/x/y/z"));
+        fs.create_dir_all("/x/y/z").unwrap();
+        fs.create("/x/f", None, false).unwrap();
+        assert_eq!(fs.create_dir_all("/x/f/g"), Err(FsError::NotADirectory));
+    }
+
+    #[test]
+    fn size_tracks_writes() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", None, false).unwrap();
+        fs.write_file("/a.txt", b"hello".to_vec(), false).unwrap();
+        assert_eq!(fs.metadata("/a.txt").unwrap().size, 5);
+        assert!(fs.get_info("/a.txt").unwrap().contains("Size: 5 B"));
+        fs.update_file("/a.txt", b"!".to_vec(), true).unwrap();
+        assert_eq!(fs.metadata("/a.txt").unwrap().size, 6);
+    }
+
+    #[test]
+    fn read_file_advances_accessed_at() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/a.txt", Some(b"a".to_vec()), false).unwrap();
+        let before = fs.metadata("/a.txt").unwrap().accessed_at;
+        clock.advance(5);
+        fs.read_file("/a.txt").unwrap();
+        let after = fs.metadata("/a.txt").unwrap().accessed_at;
+        assert_eq!(after, before + Duration::from_secs(5));
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        let mut fs = FileSystem::new();
+        fs.create("/one.txt", None, false).unwrap();
+        fs.create("/two.txt", None, false).unwrap();
+        fs.create("/three.md", None, false).unwrap();
+        assert_eq!(fs.glob("/*.txt").unwrap(), ["/one.txt", "/two.txt"]);
+    }
+
+    #[test]
+    fn glob_double_star_spans_directories() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create("/a/top.rs", None, false).unwrap();
+        fs.create("/a/b/mid.rs", None, false).unwrap();
+        fs.create("/a/b/c/deep.rs", None, false).unwrap();
+        fs.create("/a/b/c/skip.txt", None, false).unwrap();
+        assert_eq!(
+            fs.glob("/a/**/*.rs").unwrap(),
+            ["/a/b/c/deep.rs", "/a/b/mid.rs", "/a/top.rs"]
+        );
+    }
+
+    #[test]
+    fn walk_lists_paths_with_flags() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/c.txt", None, false).unwrap();
+        fs.create("/d.txt", None, false).unwrap();
+        assert_eq!(
+            fs.walk("/").unwrap(),
+            [
+                ("/a".to_string(), true),
+                ("/a/b".to_string(), true),
+                ("/a/b/c.txt".to_string(), false),
+                ("/d.txt".to_string(), false),
+            ]
+        );
+        assert_eq!(
+            fs.walk("/a/b").unwrap(),
+            [("/a/b/c.txt".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn tags_are_added_removed_and_searched() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", None, false).unwrap();
+        fs.create("/b.txt", None, false).unwrap();
+        fs.add_tag("/a.txt", "work").unwrap();
+        fs.add_tag("/a.txt", "work").unwrap();
+        fs.add_tag("/b.txt", "work").unwrap();
+        assert_eq!(fs.tags("/a.txt").unwrap(), ["work"]);
+        assert_eq!(fs.search_by_tag("work").unwrap(), ["/a.txt", "/b.txt"]);
+        fs.remove_tag("/a.txt", "work").unwrap();
+        assert!(fs.tags("/a.txt").unwrap().is_empty());
+        assert_eq!(fs.search_by_tag("work").unwrap(), ["/b.txt"]);
+    }
+
+    #[test]
+    fn mime_types_are_guessed_set_and_searched() {
+        let mut fs = FileSystem::new();
+        fs.create("/page.html", Some(Vec::new()), false).unwrap();
+        fs.create("/data.bin", Some(Vec::new()), false).unwrap();
+        assert_eq!(fs.metadata("/page.html").unwrap().mime_type, "text/html");
+        fs.set_mime_type("/data.bin", "text/html").unwrap();
+        assert_eq!(
+            fs.search_by_mime_type("text/html").unwrap(),
+            ["/data.bin", "/page.html"]
+        );
+    }
+
+    #[test]
+    fn set_owner_changes_metadata() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", None, false).unwrap();
+        fs.set_owner("/a.txt", "alice").unwrap();
+        fs.set_group("/a.txt", "staff").unwrap();
+        let metadata = fs.metadata("/a.txt").unwrap();
+        assert_eq!(metadata.owner, "alice");
+        assert_eq!(metadata.group, "staff");
+    }
+
+    #[test]
+    fn visible_listing_skips_hidden_entries() {
+        let mut fs = FileSystem::new();
+        fs.create("/a.txt", None, false).unwrap();
+        fs.create("/.dotfile", None, false).unwrap();
+        fs.create("/secret", None, false).unwrap();
+        fs.set_hidden("/secret", true).unwrap();
+        assert_eq!(
+            fs.list_directory("/").unwrap(),
+            [".dotfile", "a.txt", "secret"]
+        );
+        assert_eq!(fs.list_directory_visible("/").unwrap(), ["a.txt"]);
+    }
+
+    #[test]
+    fn truncate_shrinks_file() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"0123456789".to_vec()), false)
+            .unwrap();
+        fs.truncate("/a", 4).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"0123");
+        assert_eq!(fs.metadata("/a").unwrap().size, 4);
+    }
+
+    #[test]
+    fn truncate_pads_with_zeros() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"ab".to_vec()), false).unwrap();
+        fs.truncate("/a", 6).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"ab\0\0\0\0");
+        assert_eq!(fs.metadata("/a").unwrap().size, 6);
+    }
+
+    #[test]
+    fn read_range_reads_middle_of_file() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"hello world".to_vec()), false)
+            .unwrap();
+        assert_eq!(fs.read_range("/a", 6, 3).unwrap(), b"wor");
+    }
+
+    #[test]
+    fn read_range_clamps_past_end() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"hello".to_vec()), false).unwrap();
+        assert_eq!(fs.read_range("/a", 3, 10).unwrap(), b"lo");
+        assert!(fs.read_range("/a", 10, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn disk_usage_sums_file_sizes() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/d/sub").unwrap();
+        fs.create("/d/a", Some(vec![0; 10]), false).unwrap();
+        fs.create("/d/sub/b", Some(vec![0; 25]), false).unwrap();
+        fs.create("/outside", Some(vec![0; 100]), false).unwrap();
+        assert_eq!(fs.disk_usage("/d").unwrap(), 35);
+        assert_eq!(fs.disk_usage("/d/sub/b").unwrap(), 25);
+        assert_eq!(fs.disk_usage("/").unwrap(), 135);
+        fs.create_hard_link("/d/a", "/d/sub/a").unwrap();
+        assert_eq!(fs.disk_usage("/d").unwrap(), 35);
+        assert_eq!(fs.disk_usage("/d/sub").unwrap(), 35);
+    }
+
+    #[test]
+    fn tree_renders_exact_layout() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/docs/old").unwrap();
+        fs.create("/docs/readme.md", Some(b"hi".to_vec()), false)
+            .unwrap();
+        fs.create("/a.txt", Some(vec![0; 2048]), false).unwrap();
+        fs.create_symlink("/link", "/a.txt").unwrap();
+        fs.set_hidden("/docs/old", true).unwrap();
+        assert_eq!(
+            fs.tree("/").unwrap(),
+            "/\n\
+             ├── docs/\n\
+             │   ├── old/ (hidden)\n\
+             │   └── readme.md (2 B)\n\
+             ├── a.txt (2.0 KiB)\n\
+             └── link -> /a.txt\n"
+        );
+    }
+
+    #[test]
+    fn copy_is_deep() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/src/sub").unwrap();
+        fs.create("/src/sub/a.txt", Some(b"a".to_vec()), false)
+            .unwrap();
+        fs.create_dir_all("/dst").unwrap();
+        fs.copy("/src", "/dst", false).unwrap();
+        fs.write_file("/src/sub/a.txt", b"changed".to_vec(), false)
+            .unwrap();
+        assert_eq!(fs.read_file("/dst/src/sub/a.txt").unwrap(), b"a");
+        assert_ne!(fs.inode("/dst/src/sub/a.txt"), fs.inode("/src/sub/a.txt"));
+    }
+
+    #[test]
+    fn copy_refuses_to_overwrite_unless_asked() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/dst").unwrap();
+        fs.create("/a.txt", Some(b"new".to_vec()), false).unwrap();
+        fs.create("/dst/a.txt", Some(b"old".to_vec()), false)
+            .unwrap();
+        assert_eq!(
+            fs.copy("/a.txt", "/dst", false),
+            Err(FsError::AlreadyExists)
+        );
+        assert_eq!(fs.read_file("/dst/a.txt").unwrap(), b"old");
+        fs.copy("/a.txt", "/dst", true).unwrap();
+        assert_eq!(fs.read_file("/dst/a.txt").unwrap(), b"new");
+    }
+
+    #[test]
+    fn normalize_resolves_dot_dot() {
+        assert_eq!(normalize("/a/b/../c").unwrap(), ["a", "c"]);
+        assert_eq!(normalize("/a/./b/").unwrap(), ["a", "b"]);
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/../c", None, false).unwrap();
+        assert!(fs.is_file("/a/c"));
+    }
+
+    #[test]
+    fn normalize_rejects_climbing_above_root() {
+        assert_eq!(normalize("/.."), Err(FsError::InvalidPath));
+        assert_eq!(normalize("/a/../.."), Err(FsError::InvalidPath));
+        let mut fs = FileSystem::new();
+        assert_eq!(fs.create("/../x", None, false), Err(FsError::InvalidPath));
+    }
+
+    #[test]
+    fn symlinks_resolve_to_their_target() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/real").unwrap();
+        fs.create("/real/a.txt", Some(b"a".to_vec()), false)
+            .unwrap();
+        fs.create_symlink("/dir", "/real").unwrap();
+        fs.create_symlink("/real/rel", "a.txt").unwrap();
+        assert_eq!(fs.read_file("/dir/a.txt").unwrap(), b"a");
+        assert_eq!(fs.read_file("/real/rel").unwrap(), b"a");
+        assert_eq!(fs.read_link("/dir").unwrap(), "/real");
+    }
+
+    #[test]
+    fn symlink_loop_is_detected() {
+        let mut fs = FileSystem::new();
+        fs.create_symlink("/self", "/self").unwrap();
+        assert_eq!(fs.read_file("/self"), Err(FsError::TooManySymlinks));
+    }
+
+    #[test]
+    fn hard_links_share_writes_and_metadata() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/a", Some(b"one".to_vec()), false).unwrap();
+        fs.create_hard_link("/a", "/b").unwrap();
+        assert_eq!(fs.inode("/a"), fs.inode("/b"));
+        assert_eq!(fs.link_count("/a").unwrap(), 2);
+
+        clock.advance(10);
+        fs.write_file("/b", b"two".to_vec(), false).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"two");
+        let (a, b) = (fs.metadata("/a").unwrap(), fs.metadata("/b").unwrap());
+        assert_eq!(a.modified_at, b.modified_at);
+        assert_eq!(a.modified_at, clock.now());
+
+        fs.chmod("/a", "000").unwrap();
+        assert_eq!(fs.read_file("/b"), Err(FsError::PermissionDenied));
+        assert_eq!(fs.mode_string("/b").unwrap(), "---------");
+    }
+
+    #[test]
+    fn deleting_one_hard_link_keeps_the_other() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"data".to_vec()), false).unwrap();
+        fs.create_hard_link("/a", "/b").unwrap();
+        fs.delete("/a").unwrap();
+        assert_eq!(fs.read_file("/b").unwrap(), b"data");
+        assert_eq!(fs.link_count("/b").unwrap(), 1);
+    }
+
+    #[test]
+    fn stat_reports_node_fields() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/d").unwrap();
+        fs.create("/d/a.txt", Some(b"abc".to_vec()), false).unwrap();
+        fs.add_tag("/d/a.txt", "t").unwrap();
+        fs.create_symlink("/l", "/d/a.txt").unwrap();
+
+        let file = fs.stat("/d/a.txt").unwrap();
+        assert_eq!(file.name, "a.txt");
+        assert_eq!(file.size, 3);
+        assert!(!file.is_dir && !file.is_symlink);
+        assert_eq!(file.owner, "root");
+        assert_eq!(file.mime_type, "text/plain");
+        assert_eq!(file.tags, ["t"]);
+        assert!(fs.stat("/d").unwrap().is_dir);
+        assert!(fs.stat("/l").unwrap().is_symlink);
+        assert_eq!(fs.stat("/").unwrap().name, "/");
+    }
+
+    #[test]
+    fn chmod_accepts_octal_modes() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, false).unwrap();
+        fs.chmod("/a", "754").unwrap();
+        assert_eq!(fs.mode_string("/a").unwrap(), "rwxr-xr--");
+        fs.chmod("/a", "0o600").unwrap();
+        assert_eq!(fs.mode_string("/a").unwrap(), "rw-------");
+        assert_eq!(fs.chmod("/a", "800"), Err(FsError::InvalidMode));
+    }
+
+    #[test]
+    fn chmod_accepts_symbolic_modes() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, false).unwrap();
+        fs.chmod("/a", "r-x---rw-").unwrap();
+        assert_eq!(fs.mode_string("/a").unwrap(), "r-x---rw-");
+        assert_eq!(
+            fs.metadata("/a").unwrap().permissions,
+            Permissions::new(true, false, true)
+        );
+        assert_eq!(fs.chmod("/a", "rwxrwxrwz"), Err(FsError::InvalidMode));
+    }
+
+    #[test]
+    fn reads_follow_the_read_bit() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        fs.change_permissions("/a", Permissions::new(false, true, false))
+            .unwrap();
+        assert_eq!(fs.read_file("/a"), Err(FsError::PermissionDenied));
+        assert_eq!(fs.read_to_string("/a"), Err(FsError::PermissionDenied));
+        fs.change_permissions("/a", Permissions::new(true, true, false))
+            .unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+    }
+
+    #[test]
+    fn every_write_path_follows_the_write_bit() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"abc".to_vec()), false).unwrap();
+        fs.chmod("/a", "444").unwrap();
+        let denied = Err(FsError::PermissionDenied);
+        assert_eq!(fs.write_file("/a", b"x".to_vec(), false), denied);
+        assert_eq!(fs.update_file("/a", b"x".to_vec(), true), denied);
+        assert_eq!(fs.edit("/a", Vec::clear), denied);
+        assert_eq!(fs.truncate("/a", 0), denied);
+        assert_eq!(fs.read_file("/a").unwrap(), b"abc");
+    }
+
+    #[test]
+    fn read_only_blocks_changes_until_unset() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        fs.set_read_only("/a", true).unwrap();
+        let read_only = Err(FsError::ReadOnly);
+        assert_eq!(fs.write_file("/a", b"x".to_vec(), false), read_only);
+        assert_eq!(fs.update_file("/a", b"x".to_vec(), false), read_only);
+        assert_eq!(fs.truncate("/a", 0), read_only);
+        assert_eq!(fs.rename("/a", "b"), read_only);
+        assert_eq!(fs.delete("/a"), read_only);
+
+        fs.set_read_only("/a", false).unwrap();
+        fs.write_file("/a", b"x".to_vec(), false).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"x");
+    }
+
+    #[test]
+    fn timestamps_come_from_the_clock() {
+        let clock = FakeClock::default();
+        clock.advance(100);
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/a", Some(Vec::new()), false).unwrap();
+        let created = fs.metadata("/a").unwrap();
+        assert_eq!(created.created_at, UNIX_EPOCH + Duration::from_secs(100));
+        assert_eq!(created.modified_at, created.created_at);
+
+        clock.advance(20);
+        fs.write_file("/a", b"x".to_vec(), false).unwrap();
+        let written = fs.metadata("/a").unwrap();
+        assert_eq!(written.created_at, created.created_at);
+        assert_eq!(written.modified_at, UNIX_EPOCH + Duration::from_secs(120));
+    }
+
+    #[test]
+    fn search_by_owner_finds_owned_files() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/home").unwrap();
+        fs.create("/home/a.txt", None, false).unwrap();
+        fs.create("/b.txt", None, false).unwrap();
+        fs.create("/c.txt", None, false).unwrap();
+        fs.set_owner("/home/a.txt", "alice").unwrap();
+        fs.set_owner("/c.txt", "alice").unwrap();
+        assert_eq!(
+            fs.search_by_owner("alice").unwrap(),
+            ["/c.txt", "/home/a.txt"]
+        );
+    }
+
+    #[test]
+    fn searches_report_full_nested_paths() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/c.txt", Some(Vec::new()), false).unwrap();
+        fs.add_tag("/a/b/c.txt", "t").unwrap();
+        fs.set_owner("/a/b/c.txt", "alice").unwrap();
+        assert_eq!(fs.search_by_tag("t").unwrap(), ["/a/b/c.txt"]);
+        assert_eq!(fs.search_by_owner("alice").unwrap(), ["/a/b/c.txt"]);
+        assert_eq!(
+            fs.search_by_mime_type("text/plain").unwrap(),
+            ["/a/b/c.txt"]
+        );
+    }
+
+    #[test]
+    fn grep_finds_matching_files() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/d").unwrap();
+        fs.create("/d/a", Some(b"needle here".to_vec()), false)
+            .unwrap();
+        fs.create("/d/b", Some(b"nothing".to_vec()), false).unwrap();
+        fs.create("/d/c", Some(b"a needle".to_vec()), false)
+            .unwrap();
+        assert_eq!(
+            fs.grep("needle", "/d").unwrap(),
+            [("/d/a".to_string(), 0), ("/d/c".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn modified_since_finds_later_changes() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/old", Some(Vec::new()), false).unwrap();
+        fs.create("/new", Some(Vec::new()), false).unwrap();
+        let since = clock.now();
+        clock.advance(1);
+        fs.write_file("/new", b"x".to_vec(), false).unwrap();
+        assert_eq!(fs.modified_since(since, "/").unwrap(), ["/new"]);
+    }
+
+    #[test]
+    fn find_by_size_filters_on_inclusive_range() {
+        let mut fs = FileSystem::new();
+        fs.create("/small", Some(vec![0; 1]), false).unwrap();
+        fs.create("/medium", Some(vec![0; 50]), false).unwrap();
+        fs.create("/large", Some(vec![0; 500]), false).unwrap();
+        assert_eq!(fs.find_by_size(10, 100, "/").unwrap(), ["/medium"]);
+        assert_eq!(fs.find_by_size(1, 50, "/").unwrap(), ["/medium", "/small"]);
+    }
+
+    #[test]
+    fn checksum_changes_after_append() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"data".to_vec()), false).unwrap();
+        let before = fs.checksum("/a").unwrap();
+        assert!(fs.verify_checksum("/a", before).unwrap());
+        fs.write_file("/a", b"!".to_vec(), true).unwrap();
+        assert_ne!(fs.checksum("/a").unwrap(), before);
+        assert!(!fs.verify_checksum("/a", before).unwrap());
+    }
+
+    #[test]
+    fn diff_reports_differing_offsets() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"abcd".to_vec()), false).unwrap();
+        fs.create("/b", Some(b"abXdE".to_vec()), false).unwrap();
+        assert_eq!(
+            fs.diff("/a", "/b").unwrap(),
+            [(2, b'c', b'X'), (4, 0, b'E')]
+        );
+        assert!(fs.diff("/a", "/a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_transaction_rolls_back() {
+        let mut fs = FileSystem::new();
+        fs.create("/keep", Some(b"k".to_vec()), false).unwrap();
+        let result = fs.transaction(|fs| {
+            fs.create("/new", None, false)?;
+            fs.write_file("/keep", b"changed".to_vec(), false)?;
+            fs.delete("/missing")
+        });
+        assert_eq!(result, Err(FsError::NotFound));
+        assert!(!fs.exists("/new"));
+        assert_eq!(fs.read_file("/keep").unwrap(), b"k");
+    }
+
+    #[test]
+    fn restore_brings_back_snapshot() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        let snapshot = fs.snapshot();
+        fs.write_file("/a", b"changed".to_vec(), false).unwrap();
+        fs.create("/b", None, true).unwrap();
+        fs.restore(snapshot).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+        assert!(!fs.exists("/b"));
+    }
+
+    #[test]
+    fn edit_changes_content_in_place() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"shout".to_vec()), false).unwrap();
+        fs.edit("/a", |content| content.make_ascii_uppercase())
+            .unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"SHOUT");
+        assert_eq!(fs.metadata("/a").unwrap().size, 5);
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        let mut fs = FileSystem::new();
+        fs.write_str("/a.txt", "héllo").unwrap();
+        assert_eq!(fs.read_to_string("/a.txt").unwrap(), "héllo");
+        fs.write_str("/a.txt", "again").unwrap();
+        assert_eq!(fs.read_to_string("/a.txt").unwrap(), "again");
+    }
+
+    #[test]
+    fn read_to_string_rejects_invalid_utf8() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(vec![0xff, 0xfe]), false).unwrap();
+        assert_eq!(fs.read_to_string("/a"), Err(FsError::InvalidUtf8));
+    }
+
+    #[test]
+    fn lines_are_read_and_appended() {
+        let mut fs = FileSystem::new();
+        fs.write_str("/a", "one\ntwo\nthree").unwrap();
+        assert_eq!(fs.read_lines("/a").unwrap(), ["one", "two", "three"]);
+        fs.append_line("/a", "four").unwrap();
+        assert_eq!(
+            fs.read_lines("/a").unwrap(),
+            ["one", "two", "three", "four"]
+        );
+        assert_eq!(fs.read_to_string("/a").unwrap(), "one\ntwo\nthree\nfour\n");
+    }
+
+    #[test]
+    fn rename_keeps_inode() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, false).unwrap();
+        fs.create("/b", None, false).unwrap();
+        let inode = fs.inode("/a").unwrap();
+        assert_ne!(inode, fs.inode("/b").unwrap());
+        fs.rename("/a", "c").unwrap();
+        assert_eq!(fs.inode("/c").unwrap(), inode);
+        assert_eq!(fs.path_of_inode(inode).as_deref(), Some("/c"));
+    }
+
+    #[test]
+    fn capacity_limits_total_content() {
+        let mut fs = FileSystem::with_capacity(10);
+        fs.create("/a", Some(vec![0; 6]), false).unwrap();
+        fs.create("/b", Some(vec![0; 4]), false).unwrap();
+        assert_eq!(fs.used_bytes(), 10);
+        assert_eq!(
+            fs.write_file("/b", vec![1], true),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(
+            fs.create("/c", Some(vec![0]), false),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(fs.read_file("/b").unwrap(), [0; 4]);
+    }
+
+    #[test]
+    fn quota_applies_to_nested_writes() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.set_quota("/a", 100).unwrap();
+        fs.create("/a/b/f", None, false).unwrap();
+        assert_eq!(
+            fs.write_file("/a/b/f", vec![0; 200], false),
+            Err(FsError::QuotaExceeded)
+        );
+        fs.write_file("/a/b/f", vec![0; 100], false).unwrap();
+        fs.create("/outside", Some(vec![0; 200]), false).unwrap();
+    }
+
+    #[test]
+    fn touch_creates_missing_file() {
+        let mut fs = FileSystem::new();
+        fs.touch("/a").unwrap();
+        assert!(fs.is_file("/a"));
+        assert!(fs.read_file("/a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn touch_updates_existing_timestamps() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/a", Some(b"keep".to_vec()), false).unwrap();
+        clock.advance(3);
+        fs.touch("/a").unwrap();
+        let metadata = fs.metadata("/a").unwrap();
+        assert_eq!(metadata.modified_at, clock.now());
+        assert_eq!(metadata.accessed_at, clock.now());
+        assert_ne!(metadata.created_at, clock.now());
+        assert_eq!(fs.read_file("/a").unwrap(), b"keep");
+    }
+
+    #[test]
+    fn xattrs_are_set_read_and_removed() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, false).unwrap();
+        fs.set_xattr("/a", "user.b", b"2").unwrap();
+        fs.set_xattr("/a", "user.a", b"1").unwrap();
+        assert_eq!(fs.get_xattr("/a", "user.a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(fs.list_xattr("/a").unwrap(), ["user.a", "user.b"]);
+        fs.remove_xattr("/a", "user.a").unwrap();
+        assert_eq!(fs.get_xattr("/a", "user.a").unwrap(), None);
+        assert_eq!(fs.list_xattr("/a").unwrap(), ["user.b"]);
+    }
+
+    #[test]
+    fn merge_resolves_conflicts_by_overwrite_flag() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/src/sub").unwrap();
+        fs.create_dir_all("/dst/sub").unwrap();
+        fs.create("/src/sub/both", Some(b"src".to_vec()), false)
+            .unwrap();
+        fs.create("/src/sub/new", Some(b"n".to_vec()), false)
+            .unwrap();
+        fs.create("/dst/sub/both", Some(b"dst".to_vec()), false)
+            .unwrap();
+        fs.create("/dst/sub/kept", None, false).unwrap();
+
+        fs.merge("/src", "/dst", false).unwrap();
+        assert_eq!(fs.read_file("/dst/sub/both").unwrap(), b"dst");
+        assert_eq!(
+            fs.list_directory("/dst/sub").unwrap(),
+            ["both", "kept", "new"]
+        );
+        fs.merge("/src", "/dst", true).unwrap();
+        assert_eq!(fs.read_file("/dst/sub/both").unwrap(), b"src");
+        assert!(fs.exists("/dst/sub/kept"));
+    }
+
+    #[test]
+    fn tree_diff_reports_added_removed_and_modified() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a").unwrap();
+        fs.create_dir_all("/b").unwrap();
+        fs.create("/a/same", Some(b"s".to_vec()), false).unwrap();
+        fs.create("/b/same", Some(b"s".to_vec()), false).unwrap();
+        fs.create("/a/changed", Some(b"1".to_vec()), false).unwrap();
+        fs.create("/b/changed", Some(b"2".to_vec()), false).unwrap();
+        fs.create("/a/removed", None, false).unwrap();
+        fs.create("/b/added", None, false).unwrap();
+        assert_eq!(
+            fs.tree_diff("/a", "/b").unwrap(),
+            TreeDiff {
+                only_in_a: vec!["removed".to_string()],
+                only_in_b: vec!["added".to_string()],
+                changed: vec!["changed".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn sync_mirrors_divergent_directory() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/src/sub").unwrap();
+        fs.create_dir_all("/dst/extra").unwrap();
+        fs.create("/src/sub/new", Some(b"n".to_vec()), false)
+            .unwrap();
+        fs.create("/src/changed", Some(b"new".to_vec()), false)
+            .unwrap();
+        fs.create("/dst/changed", Some(b"old".to_vec()), false)
+            .unwrap();
+        fs.create("/dst/extra/gone", None, false).unwrap();
+
+        let report = fs.sync("/src", "/dst").unwrap();
+        assert_eq!(report.created, ["sub"]);
+        assert_eq!(report.updated, ["changed"]);
+        assert_eq!(report.deleted, ["extra"]);
+        assert_eq!(fs.tree_diff("/src", "/dst").unwrap(), TreeDiff::default());
+        assert_eq!(fs.read_file("/dst/sub/new").unwrap(), b"n");
+    }
+
+    #[test]
+    fn entries_report_name_type_size_and_mtime() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/b.txt", Some(b"hello".to_vec()), false).unwrap();
+        clock.advance(10);
+        fs.create("/a", None, true).unwrap();
+        let dir_size = fs.metadata("/a").unwrap().size;
+        assert_eq!(
+            fs.entries("/").unwrap(),
+            [
+                DirEntry {
+                    name: "a".to_string(),
+                    is_dir: true,
+                    size: dir_size,
+                    modified_at: UNIX_EPOCH + Duration::from_secs(10),
+                },
+                DirEntry {
+                    name: "b.txt".to_string(),
+                    is_dir: false,
+                    size: 5,
+                    modified_at: UNIX_EPOCH,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn list_sorted_by_name_and_size() {
+        let mut fs = FileSystem::new();
+        fs.create("/b", Some(vec![0; 1]), false).unwrap();
+        fs.create("/c", Some(vec![0; 3]), false).unwrap();
+        fs.create("/a", Some(vec![0; 2]), false).unwrap();
+        fs.create("/z", None, true).unwrap();
+        assert_eq!(
+            fs.list_sorted("/", SortKey::Name, Order::Asc).unwrap(),
+            ["z", "a", "b", "c"]
+        );
+        assert_eq!(
+            fs.list_sorted("/", SortKey::Size, Order::Desc).unwrap(),
+            ["z", "c", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn find_filters_by_stat() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/var/log").unwrap();
+        fs.create("/var/log/big.log", Some(vec![b'x'; 20]), false)
+            .unwrap();
+        fs.create("/var/log/small.log", Some(vec![b'x'; 5]), false)
+            .unwrap();
+        fs.create("/var/big.txt", Some(vec![b'x'; 20]), false)
+            .unwrap();
+        let before = fs.metadata("/var/log/big.log").unwrap().accessed_at;
+        let found = fs
+            .find("/", |stat| stat.size > 10 && stat.name.ends_with(".log"))
+            .unwrap();
+        assert_eq!(found, ["/var/log/big.log"]);
+        let after = fs.metadata("/var/log/big.log").unwrap().accessed_at;
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn copy_with_progress_reports_each_node_and_cancels() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/src/sub").unwrap();
+        fs.create("/src/a", None, false).unwrap();
+        fs.create("/src/sub/b", None, false).unwrap();
+        fs.create("/dst", None, true).unwrap();
+
+        let mut calls = Vec::new();
+        fs.copy_with_progress("/src", "/dst", |done, total| {
+            calls.push((done, total));
+            true
+        })
+        .unwrap();
+        assert_eq!(calls, [(1, 4), (2, 4), (3, 4), (4, 4)]);
+        assert!(fs.exists("/dst/src/sub/b"));
+
+        let err = fs
+            .copy_with_progress("/src", "/", |done, _| done < 2)
+            .unwrap_err();
+        assert_eq!(err, FsError::AlreadyExists);
+        fs.create("/other", None, true).unwrap();
+        let err = fs
+            .copy_with_progress("/src", "/other", |done, _| done < 2)
+            .unwrap_err();
+        assert_eq!(err, FsError::Cancelled);
+        assert!(fs.is_empty_dir("/other").unwrap());
+    }
+
+    #[test]
+    fn human_size_formats_binary_units() {
+        let cases = [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (1024 * 1024 - 1, "1.0 MiB"),
+            (2 * 1024 * 1024, "2.0 MiB"),
+            (3 << 30, "3.0 GiB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(human_size(bytes), expected, "{} bytes", bytes);
+        }
+    }
+
+    #[test]
+    fn path_helpers_normalize_components() {
+        assert_eq!(parent("/a/b/c").as_deref(), Some("/a/b"));
+        assert_eq!(parent("/a").as_deref(), Some("/"));
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent("/a/b/").as_deref(), Some("/a"));
+        assert_eq!(basename("/a/b/c").as_deref(), Some("c"));
+        assert_eq!(basename("/"), None);
+        assert_eq!(basename("/a/b/").as_deref(), Some("b"));
+        assert_eq!(join("/a/b", "c/d"), "/a/b/c/d");
+        assert_eq!(join("/", "a"), "/a");
+        assert_eq!(join("/a/", "/b/"), "/a/b");
+        assert_eq!(join("/a", "../.."), "/");
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut fs = FileSystem::new();
+        let long = format!("/{}", "a".repeat(300));
+        assert_eq!(fs.create("/a\0b", None, false), Err(FsError::InvalidPath));
+        assert_eq!(fs.create(&long, None, false), Err(FsError::InvalidPath));
+        fs.create("/ok", None, false).unwrap();
+        assert_eq!(fs.rename("/ok", "a\0b"), Err(FsError::InvalidPath));
+        assert_eq!(fs.rename("/ok", &long[1..]), Err(FsError::InvalidPath));
+        assert_eq!(fs.list_directory("/").unwrap(), ["ok"]);
+    }
+
+    #[test]
+    fn case_sensitive_by_default() {
+        let mut fs = FileSystem::new();
+        fs.create("/A.txt", Some(b"upper".to_vec()), false).unwrap();
+        assert_eq!(fs.read_file("/a.txt"), Err(FsError::NotFound));
+        fs.create("/a.txt", Some(b"lower".to_vec()), false).unwrap();
+        assert_eq!(fs.list_directory("/").unwrap(), ["A.txt", "a.txt"]);
+        assert_eq!(fs.glob("/a.*").unwrap(), ["/a.txt"]);
+    }
+
+    #[test]
+    fn case_insensitive_mode_folds_lookups_and_globs() {
+        let mut fs = FileSystem::new_case_insensitive();
+        fs.create_dir_all("/Logs").unwrap();
+        fs.create("/A.txt", Some(b"upper".to_vec()), false).unwrap();
+        assert_eq!(fs.read_file("/a.txt").unwrap(), b"upper");
+        assert_eq!(
+            fs.create("/a.txt", None, false),
+            Err(FsError::AlreadyExists)
+        );
+        assert_eq!(fs.list_directory("/").unwrap(), ["A.txt", "Logs"]);
+
+        fs.create("/logs/One.LOG", None, false).unwrap();
+        fs.create("/logs/two.log", None, false).unwrap();
+        assert_eq!(fs.glob("/a.*").unwrap(), ["/A.txt"]);
+        assert_eq!(
+            fs.glob("/logs/*.log").unwrap(),
+            ["/Logs/One.LOG", "/Logs/two.log"]
+        );
+        assert_eq!(fs.delete_glob("/LOGS/*.Log", false).unwrap(), 2);
+        assert!(fs.is_empty_dir("/Logs").unwrap());
+    }
+
+    #[test]
+    fn limits_bound_depth_and_entries_per_dir() {
+        let mut fs = FileSystem::with_limits(Limits {
+            max_depth: 2,
+            max_entries_per_dir: 2,
+            ..Limits::default()
+        });
+        fs.create_dir_all("/a/b").unwrap();
+        assert_eq!(
+            fs.create("/a/b/c", None, false),
+            Err(FsError::LimitExceeded)
+        );
+        assert_eq!(fs.create_dir_all("/x/y/z"), Err(FsError::LimitExceeded));
+        assert!(fs.exists("/x/y"));
+
+        fs.create("/a/one", None, false).unwrap();
+        assert_eq!(
+            fs.create("/a/two", None, false),
+            Err(FsError::LimitExceeded)
+        );
+        assert_eq!(fs.entry_count("/a").unwrap(), 2);
+        assert_eq!(
+            fs.copy("/a/one", "/a/b", false),
+            Err(FsError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn copy_and_move_into_descendant_fail() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/sub").unwrap();
+        fs.create("/a/f", Some(b"x".to_vec()), false).unwrap();
+        let before = fs.clone();
+        assert_eq!(fs.copy("/a", "/a/sub", false), Err(FsError::InvalidPath));
+        assert_eq!(fs.copy("/a", "/a", false), Err(FsError::InvalidPath));
+        assert_eq!(fs.move_node("/a", "/a/sub"), Err(FsError::InvalidPath));
+        assert_eq!(fs, before);
+        assert_eq!(fs.list_directory("/a/sub").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn create_many_builds_nested_files() {
+        let mut fs = FileSystem::new();
+        let files = [
+            "/a.txt",
+            "/docs/b.txt",
+            "/docs/c.txt",
+            "/src/lib/d.rs",
+            "/src/lib/deep/e.rs",
+        ];
+        let map = files
+            .iter()
+            .map(|path| (path.to_string(), path.as_bytes().to_vec()))
+            .collect();
+        fs.create_many(map).unwrap();
+        for path in files {
+            assert_eq!(fs.read_file(path).unwrap(), path.as_bytes());
+        }
+
+        let clash = HashMap::from([
+            ("/new/x".to_string(), Vec::new()),
+            ("/a.txt/y".to_string(), Vec::new()),
+        ]);
+        assert!(fs.create_many(clash).is_err());
+        assert!(!fs.exists("/new"));
+    }
+
+    #[test]
+    fn clone_then_mutate_differs() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        let mut copy = fs.clone();
+        assert_eq!(copy, fs);
+        copy.write_file("/a", b"b".to_vec(), false).unwrap();
+        assert_ne!(copy, fs);
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+    }
+
+    #[test]
+    fn default_filesystem_is_empty() {
+        let mut fs = FileSystem::default();
+        assert!(fs.list_directory("/").unwrap().is_empty());
+        assert_eq!(fs, FileSystem::new());
+        fs.create("/a", None, false).unwrap();
+        assert_eq!(
+            fs.metadata("/a").unwrap().permissions,
+            Permissions::default()
+        );
+        assert_eq!(fs.default_permissions(), Permissions::default());
+    }
+
+    #[test]
+    fn root_operations() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", None, true).unwrap();
+        fs.create("/b", None, false).unwrap();
+        let stat = fs.stat("/").unwrap();
+        assert!(stat.is_dir);
+        assert_eq!(stat.name, "/");
+        assert!(fs.get_info("/").unwrap().starts_with("Directory Name: /"));
+        assert_eq!(fs.inode("/").unwrap(), 1);
+        assert_eq!(fs.list_directory("/").unwrap(), ["a", "b"]);
+        assert_eq!(fs.delete("/"), Err(FsError::InvalidPath));
+        assert_eq!(fs.delete_recursive("/"), Err(FsError::InvalidPath));
+        assert_eq!(fs.list_directory("/").unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn stats_count_known_tree() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create("/empty", None, true).unwrap();
+        fs.create("/a/one", Some(vec![0; 3]), false).unwrap();
+        fs.create("/a/b/c/two", Some(vec![0; 4]), false).unwrap();
+        fs.create_symlink("/link", "/a/one").unwrap();
+        assert_eq!(
+            fs.stats("/").unwrap(),
+            FsStats {
+                file_count: 2,
+                dir_count: 4,
+                symlink_count: 1,
+                total_bytes: 7,
+                max_depth: 4,
+            }
+        );
+        assert_eq!(fs.stats("/empty").unwrap(), FsStats::default());
+    }
+
+    #[test]
+    fn memory_usage_grows_with_tagged_files() {
+        let mut fs = FileSystem::new();
+        let empty = fs.memory_usage();
+        fs.create("/a", Some(vec![0; 100]), false).unwrap();
+        let one = fs.memory_usage();
+        assert!(one >= empty + 100);
+        fs.add_tag("/a", "important").unwrap();
+        assert!(fs.memory_usage() > one);
+        assert!(fs.memory_usage() > fs.used_bytes());
+    }
+
+    #[test]
+    fn to_dot_declares_nodes_and_edges() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/docs").unwrap();
+        fs.create("/docs/say \"hi\".txt", None, false).unwrap();
+        let dot = fs.to_dot("/").unwrap();
+        assert!(dot.starts_with("digraph filesystem {\n"));
+        assert!(dot.contains("    \"/\" [label=\"/\", shape=folder];\n"));
+        assert!(dot.contains("    \"/docs\" [label=\"docs\", shape=folder];\n"));
+        assert!(dot.contains("    \"/\" -> \"/docs\";\n"));
+        assert!(dot.contains(
+            "    \"/docs/say \\\"hi\\\".txt\" [label=\"say \\\"hi\\\".txt\", shape=note];\n"
+        ));
+        assert!(dot.contains("    \"/docs\" -> \"/docs/say \\\"hi\\\".txt\";\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn insert_at_and_remove_range_shift_content() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"hello world".to_vec()), false)
+            .unwrap();
+        fs.insert_at("/a", 5, b",").unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"hello, world");
+        fs.remove_range("/a", 5, 7).unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"hello");
+        assert_eq!(fs.metadata("/a").unwrap().size, 5);
+
+        assert_eq!(fs.insert_at("/a", 6, b"x"), Err(FsError::InvalidOffset));
+        assert_eq!(fs.remove_range("/a", 3, 3), Err(FsError::InvalidOffset));
+        assert_eq!(
+            fs.remove_range("/a", 1, usize::MAX),
+            Err(FsError::InvalidOffset)
+        );
+        assert_eq!(fs.read_file("/a").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn copy_with_options_preserves_or_refreshes_timestamps() {
+        let clock = FakeClock::default();
+        let mut fs = FileSystem::with_clock(Box::new(clock.clone()));
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        fs.create_dir_all("/kept").unwrap();
+        fs.create_dir_all("/fresh").unwrap();
+        clock.advance(100);
+        let later = UNIX_EPOCH + Duration::from_secs(100);
+
+        let preserve = CopyOptions {
+            preserve_timestamps: true,
+            ..CopyOptions::default()
+        };
+        fs.copy_with_options("/a", "/kept", preserve).unwrap();
+        let kept = fs.metadata("/kept/a").unwrap();
+        assert_eq!(kept.created_at, UNIX_EPOCH);
+        assert_eq!(kept.modified_at, UNIX_EPOCH);
+
+        fs.copy_with_options("/a", "/fresh", CopyOptions::default())
+            .unwrap();
+        let fresh = fs.metadata("/fresh/a").unwrap();
+        assert_eq!(fresh.created_at, later);
+        assert_eq!(fresh.modified_at, later);
+    }
+
+    #[test]
+    fn tag_all_tags_every_file_in_subtree() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/proj/src").unwrap();
+        fs.create("/proj/readme", None, false).unwrap();
+        fs.create("/proj/src/main.rs", None, false).unwrap();
+        fs.create("/outside", None, false).unwrap();
+        fs.add_tag("/proj/readme", "review").unwrap();
+
+        assert_eq!(fs.tag_all("/proj", "review").unwrap(), 1);
+        assert_eq!(
+            fs.search_by_tag("review").unwrap(),
+            ["/proj/readme", "/proj/src/main.rs"]
+        );
+        assert!(fs.tags("/proj/src").unwrap().is_empty());
+
+        assert_eq!(fs.untag_all("/proj/src", "review").unwrap(), 1);
+        assert_eq!(fs.search_by_tag("review").unwrap(), ["/proj/readme"]);
+    }
+
+    #[test]
+    fn freeze_blocks_writes_until_thawed() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        fs.create("/old", None, false).unwrap();
+        fs.trash("/old").unwrap();
+        let snapshot = fs.snapshot();
+        fs.freeze();
+        assert!(fs.is_frozen());
+        assert_eq!(
+            fs.write_file("/a", b"b".to_vec(), false),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(fs.create("/b", None, false), Err(FsError::PermissionDenied));
+        fs.thaw();
+        fs.write_file("/a", b"b".to_vec(), false).unwrap();
+
+        fs.freeze();
+        assert_eq!(fs.restore(snapshot), Err(FsError::PermissionDenied));
+        assert_eq!(fs.read_file("/a").unwrap(), b"b");
+        assert_eq!(fs.empty_trash(), Err(FsError::PermissionDenied));
+        assert_eq!(fs.trashed(), ["/old"]);
+        fs.thaw();
+        fs.empty_trash().unwrap();
+        assert!(fs.trashed().is_empty());
+    }
+
+    #[test]
+    fn listing_order_ignores_insertion_order() {
+        let names = ["delta", "alpha", "Charlie", "bravo", "echo"];
+        let mut orders = Vec::new();
+        for rotation in 0..names.len() {
+            let mut fs = FileSystem::new();
+            fs.create("/dir", None, true).unwrap();
+            for i in 0..names.len() {
+                let name = names[(rotation + i) % names.len()];
+                fs.create(&format!("/dir/{}", name), None, i % 2 == 0)
+                    .unwrap();
+            }
+            let walked = fs
+                .walk("/dir")
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>();
+            let entries = fs.entries("/dir").unwrap();
+            let entries = entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+            orders.push((fs.list_directory("/dir").unwrap(), walked, entries));
+        }
+        let (listed, walked, entries) = &orders[0];
+        assert_eq!(listed, &["Charlie", "alpha", "bravo", "delta", "echo"]);
+        assert_eq!(entries, listed);
+        assert_eq!(walked[0], "/dir/Charlie");
+        assert!(orders.iter().all(|order| order == &orders[0]));
+    }
+
+    #[test]
+    fn rename_takes_bare_name_or_full_path() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a").unwrap();
+        fs.create_dir_all("/b").unwrap();
+        fs.create("/a/x.txt", Some(b"x".to_vec()), false).unwrap();
+        fs.rename("/a/x.txt", "y.txt").unwrap();
+        assert_eq!(fs.list_directory("/a").unwrap(), ["y.txt"]);
+        fs.rename("/a/y.txt", "/a/z.txt").unwrap();
+        assert_eq!(fs.list_directory("/a").unwrap(), ["z.txt"]);
+        fs.rename("/a/z.txt", "/b/w.txt").unwrap();
+        assert!(fs.is_empty_dir("/a").unwrap());
+        assert_eq!(fs.read_file("/b/w.txt").unwrap(), b"x");
+    }
+
+    #[test]
+    fn path_errors_distinguish_missing_from_file_components() {
+        let mut fs = FileSystem::new();
+        fs.create("/file", None, false).unwrap();
+        assert_eq!(fs.list_directory("/missing/x"), Err(FsError::NotFound));
+        assert_eq!(fs.create("/missing/x", None, false), Err(FsError::NotFound));
+        assert_eq!(fs.list_directory("/file/x"), Err(FsError::NotADirectory));
+        assert_eq!(
+            fs.create("/file/x", None, false),
+            Err(FsError::NotADirectory)
+        );
+        assert_eq!(fs.list_directory("/file"), Err(FsError::NotADirectory));
+    }
+
+    #[test]
+    fn walk_iter_stops_early() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b/c/d/e").unwrap();
+        fs.create("/z", None, false).unwrap();
+        let mut walk = fs.walk_iter("/").unwrap();
+        let first = walk.by_ref().take(2).collect::<Vec<_>>();
+        assert_eq!(
+            first,
+            [("/a".to_string(), true), ("/a/b".to_string(), true)]
+        );
+        // Only the directories on the way to the last entry are open.
+        assert_eq!(walk.stack.len(), 3);
+        assert_eq!(walk.last(), Some(("/z".to_string(), false)));
+    }
+
+    #[test]
+    fn relative_path_between_nodes() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create_dir_all("/x/y").unwrap();
+        fs.create("/a/one", None, false).unwrap();
+        fs.create("/a/two", None, false).unwrap();
+        fs.create("/x/y/far.txt", None, false).unwrap();
+        assert_eq!(fs.relative_path("/a/one", "/a/two").unwrap(), "two");
+        assert_eq!(fs.relative_path("/a/b/c", "/a").unwrap(), "../..");
+        assert_eq!(fs.relative_path("/a", "/a/b/c").unwrap(), "b/c");
+        assert_eq!(fs.relative_path("/a", "/a").unwrap(), ".");
+        assert_eq!(
+            fs.relative_path("/a/b/c", "/x/y/far.txt").unwrap(),
+            "../../../x/y/far.txt"
+        );
+        assert_eq!(
+            fs.relative_path("/a/one", "/missing"),
+            Err(FsError::NotFound)
+        );
+    }
+
+    #[test]
+    fn canonicalize_follows_links_and_checks_existence() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/real/sub").unwrap();
+        fs.create("/real/sub/f", None, false).unwrap();
+        fs.create_symlink("/link", "/real/sub").unwrap();
+        assert_eq!(fs.canonicalize("/link/./f").unwrap(), "/real/sub/f");
+        assert_eq!(fs.canonicalize("/link/../sub").unwrap(), "/real/sub");
+        assert_eq!(fs.canonicalize("/link").unwrap(), "/real/sub");
+        assert_eq!(fs.canonicalize("/link/missing"), Err(FsError::NotFound));
+        assert_eq!(fs.canonicalize("/real/missing/f"), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn delete_glob_removes_matching_logs() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/tmp/sub").unwrap();
+        fs.create("/tmp/a.log", None, false).unwrap();
+        fs.create("/tmp/b.log", None, false).unwrap();
+        fs.create("/tmp/keep.txt", None, false).unwrap();
+        fs.create("/tmp/sub/c.log", None, false).unwrap();
+        assert_eq!(fs.delete_glob("/tmp/*.log", false).unwrap(), 2);
+        assert_eq!(fs.list_directory("/tmp").unwrap(), ["keep.txt", "sub"]);
+        assert_eq!(fs.delete_glob("/tmp/*", false).unwrap(), 1);
+        assert!(fs.exists("/tmp/sub/c.log"));
+
+        // Everything below /tmp matches, so /tmp/sub is empty in time.
+        assert_eq!(fs.delete_glob("/tmp/**", false).unwrap(), 2);
+        assert!(fs.is_empty_dir("/tmp").unwrap());
+    }
+
+    #[test]
+    fn entry_count_and_is_empty_dir() {
+        let mut fs = FileSystem::new();
+        fs.create("/empty", None, true).unwrap();
+        fs.create_dir_all("/full/sub").unwrap();
+        fs.create("/full/a", None, false).unwrap();
+        fs.create("/full/sub/b", None, false).unwrap();
+        assert_eq!(fs.entry_count("/empty").unwrap(), 0);
+        assert!(fs.is_empty_dir("/empty").unwrap());
+        assert_eq!(fs.entry_count("/full").unwrap(), 2);
+        assert!(!fs.is_empty_dir("/full").unwrap());
+        assert_eq!(fs.entry_count("/full/a"), Err(FsError::NotADirectory));
+        assert_eq!(fs.is_empty_dir("/full/a"), Err(FsError::NotADirectory));
+        assert_eq!(fs.entry_count("/missing"), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn default_permissions_and_umask_apply_to_new_nodes() {
+        let mut fs = FileSystem::new();
+        fs.create("/before", None, false).unwrap();
+        let root_mode = fs.mode_string("/").unwrap();
+        fs.set_default_permissions(Permissions::new(true, false, false));
+        fs.create("/ro", Some(b"x".to_vec()), false).unwrap();
+        assert_eq!(fs.mode_string("/ro").unwrap(), "r--r--r--");
+        assert_eq!(
+            fs.write_file("/ro", b"y".to_vec(), false),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(fs.mode_string("/before").unwrap(), "rw-r--r--");
+        assert_eq!(fs.mode_string("/").unwrap(), root_mode);
+
+        fs.set_default_permissions(Permissions::new(true, true, true));
+        fs.set_umask(0o077);
+        fs.create("/private", None, true).unwrap();
+        assert_eq!(fs.mode_string("/private").unwrap(), "rwx------");
+    }
+
+    #[test]
+    fn children_inherit_owner_from_parent() {
+        let mut fs = FileSystem::new();
+        fs.create("/home", None, true).unwrap();
+        fs.set_owner("/home", "alice").unwrap();
+        fs.set_group("/home", "staff").unwrap();
+        fs.chmod("/home", "rwxr-x---").unwrap();
+        fs.create("/home/before", None, false).unwrap();
+        assert_ne!(fs.metadata("/home/before").unwrap().owner, "alice");
+
+        fs.set_inherit_metadata(true);
+        fs.create("/home/notes", None, false).unwrap();
+        fs.create_dir_all("/home/docs/deep").unwrap();
+        for path in ["/home/notes", "/home/docs/deep"] {
+            let metadata = fs.metadata(path).unwrap();
+            assert_eq!(metadata.owner, "alice");
+            assert_eq!(metadata.group, "staff");
+            assert_eq!(fs.mode_string(path).unwrap(), "rwxr-x---");
+        }
+    }
+
+    #[test]
+    fn case_insensitive_move_can_change_case() {
+        let mut fs = FileSystem::new_case_insensitive();
+        fs.create_dir_all("/a").unwrap();
+        fs.create("/a/File", Some(b"x".to_vec()), false).unwrap();
+        fs.create("/a/other", None, false).unwrap();
+        fs.move_node("/a/File", "/a/FILE").unwrap();
+        assert_eq!(fs.list_directory("/a").unwrap(), ["FILE", "other"]);
+        assert_eq!(fs.read_file("/a/file").unwrap(), b"x");
+        fs.rename("/a/file", "/a/File").unwrap();
+        assert_eq!(fs.list_directory("/a").unwrap(), ["File", "other"]);
+        assert_eq!(
+            fs.move_node("/a/File", "/a/OTHER"),
+            Err(FsError::AlreadyExists)
+        );
     }
 }
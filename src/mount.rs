@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::mem;
+
+use crate::{check_room, join, normalize, FileSystem, FsError, FsEventKind, Metadata};
+
+/// A filesystem grafted into the tree with [`FileSystem::mount`]. While
+/// mounted, its entries live in the mount point directory; `sub` keeps its
+/// settings and `covered` the metadata and quota the mount point had before.
+#[derive(Debug, Clone)]
+pub(crate) struct Mount {
+    path: Vec<String>,
+    sub: FileSystem,
+    covered: (Metadata, Option<usize>),
+}
+
+impl FileSystem {
+    /// Mounts `sub` on the empty directory at `mount_point`. Its entries
+    /// then show up below the mount point and every operation works on
+    /// them as on any other node; the mount point takes on the metadata and
+    /// quota of `sub`'s root. Mounted nodes get fresh inodes, and the
+    /// capacity, quotas and limits of this filesystem apply to them. The
+    /// mount is reported as a metadata change of the mount point followed
+    /// by the creation of each entry.
+    pub fn mount(&mut self, mount_point: &str, mut sub: FileSystem) -> Result<(), FsError> {
+        let path = self.resolve(&normalize(mount_point)?, true)?;
+        if path.is_empty() {
+            return Err(FsError::InvalidPath);
+        }
+        let dir = self.navigate_physical(&path)?;
+        if !dir.nodes.is_empty() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+        let height = sub.root.nodes.values().map(|node| node.height()).max();
+        self.check_limits(&path, height.unwrap_or(0), false, sub.root.node_count())?;
+        let room = self.room_in(&path, 0);
+        check_room(room, 0, sub.root.content_bytes(&mut HashSet::new()))?;
+
+        let mut nodes = mem::take(&mut sub.root.nodes);
+        for node in nodes.values_mut() {
+            self.reassign_inodes(node);
+        }
+        let dir = self.navigate_to_directory(&path)?;
+        let metadata = Metadata {
+            inode: dir.metadata.inode,
+            ..sub.root.metadata.clone()
+        };
+        let covered = (
+            mem::replace(&mut dir.metadata, metadata),
+            mem::replace(&mut dir.quota, sub.root.quota),
+        );
+        let names = nodes.keys().cloned().collect::<Vec<_>>();
+        dir.nodes = nodes;
+        let mount_point = format!("/{}", path.join("/"));
+        self.mounts.push(Mount { path, sub, covered });
+        self.notify(FsEventKind::Metadata, &mount_point);
+        for name in names {
+            self.notify(FsEventKind::Create, &join(&mount_point, &name));
+        }
+        Ok(())
+    }
+
+    /// Detaches the filesystem mounted at `mount_point` and hands it back
+    /// with every change made through the mount. The mount point returns to
+    /// the empty directory it was before. This is reported as the deletion
+    /// of each entry followed by a metadata change of the mount point.
+    pub fn unmount(&mut self, mount_point: &str) -> Result<FileSystem, FsError> {
+        self.check_thawed()?;
+        let path = self.resolve(&normalize(mount_point)?, true)?;
+        let index = self
+            .mounts
+            .iter()
+            .rposition(|mount| mount.path == path)
+            .ok_or(FsError::NotFound)?;
+        let Mount {
+            path,
+            mut sub,
+            covered: (metadata, quota),
+        } = self.mounts.remove(index);
+        let mount_point = format!("/{}", path.join("/"));
+        let dir = self.navigate_to_directory(&path)?;
+        sub.root.nodes = mem::take(&mut dir.nodes);
+        sub.root.metadata = mem::replace(&mut dir.metadata, metadata);
+        sub.root.metadata.inode = 1;
+        sub.root.quota = mem::replace(&mut dir.quota, quota);
+        sub.next_inode = sub.next_inode.max(self.next_inode);
+        sub.rebuild_indexes();
+        for name in sub.root.nodes.keys() {
+            self.notify(FsEventKind::Delete, &join(&mount_point, name));
+        }
+        self.notify(FsEventKind::Metadata, &mount_point);
+        Ok(sub)
+    }
+
+    /// The resolved paths of the current mount points, in mount order.
+    pub fn mounts(&self) -> Vec<String> {
+        self.mounts
+            .iter()
+            .map(|mount| format!("/{}", mount.path.join("/")))
+            .collect()
+    }
+
+    /// Fails with `Busy` when removing or moving the node at `path` would
+    /// take a mount point with it.
+    pub(crate) fn check_unmounted(&self, path: &str) -> Result<(), FsError> {
+        let parts = self.resolve(&normalize(path)?, false)?;
+        if self
+            .mounts
+            .iter()
+            .any(|mount| mount.path.starts_with(&parts))
+        {
+            return Err(FsError::Busy);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FileSystem, FsError};
+
+    fn populated() -> FileSystem {
+        let mut sub = FileSystem::new();
+        sub.create_dir_all("/docs").unwrap();
+        sub.create("/docs/readme", Some(b"hello".to_vec()), false)
+            .unwrap();
+        sub
+    }
+
+    #[test]
+    fn reads_through_mount_point() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/mnt/data").unwrap();
+        fs.mount("/mnt/data", populated()).unwrap();
+        assert_eq!(fs.mounts(), ["/mnt/data"]);
+        assert_eq!(fs.list_directory("/mnt/data").unwrap(), ["docs"]);
+        assert_eq!(fs.read_file("/mnt/data/docs/readme").unwrap(), b"hello");
+        fs.write_file("/mnt/data/docs/readme", b"!".to_vec(), true)
+            .unwrap();
+
+        let sub = fs.unmount("/mnt/data").unwrap();
+        assert!(fs.is_empty_dir("/mnt/data").unwrap());
+        assert_eq!(sub.read_to_string("/docs/readme").unwrap(), "hello!");
+    }
+
+    #[test]
+    fn mount_point_cannot_be_replaced() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/mnt/data").unwrap();
+        fs.create_dir_all("/other/data").unwrap();
+        fs.create_dir_all("/other/mnt").unwrap();
+        fs.mount("/mnt/data", populated()).unwrap();
+        assert_eq!(fs.copy("/other/data", "/mnt", true), Err(FsError::Busy));
+        assert_eq!(fs.copy("/other/mnt", "/", true), Err(FsError::Busy));
+        assert_eq!(fs.delete_recursive("/mnt"), Err(FsError::Busy));
+        assert_eq!(fs.read_file("/mnt/data/docs/readme").unwrap(), b"hello");
+        assert_eq!(fs.mounts(), ["/mnt/data"]);
+    }
+
+    #[test]
+    fn failed_transaction_undoes_mount() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/mnt").unwrap();
+        let result = fs.transaction(|fs| {
+            fs.mount("/mnt", populated())?;
+            Err(FsError::Cancelled)
+        });
+        assert_eq!(result, Err(FsError::Cancelled));
+        assert!(fs.mounts().is_empty());
+        assert!(fs.is_empty_dir("/mnt").unwrap());
+        assert_eq!(fs.unmount("/mnt").err(), Some(FsError::NotFound));
+        fs.delete("/mnt").unwrap();
+    }
+
+    #[test]
+    fn restore_brings_back_mounts() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/mnt").unwrap();
+        let before = fs.snapshot();
+        fs.mount("/mnt", populated()).unwrap();
+        let mounted = fs.snapshot();
+
+        fs.restore(before).unwrap();
+        assert!(fs.mounts().is_empty());
+        assert!(fs.is_empty_dir("/mnt").unwrap());
+
+        fs.restore(mounted).unwrap();
+        assert_eq!(fs.mounts(), ["/mnt"]);
+        let sub = fs.unmount("/mnt").unwrap();
+        assert_eq!(sub.read_to_string("/docs/readme").unwrap(), "hello");
+    }
+}
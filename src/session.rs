@@ -0,0 +1,126 @@
+use crate::{parent, Access, FileSystem, FsError};
+
+/// A view of a [`FileSystem`] acting as one user, returned by
+/// [`FileSystem::as_user`]. Every operation first checks the user's access
+/// with [`FileSystem::access_as`] and fails with `PermissionDenied` when it
+/// is missing; the checks of the underlying call still apply on top.
+/// Creating or deleting an entry needs write access to its directory.
+pub struct UserSession<'a> {
+    fs: &'a mut FileSystem,
+    user: String,
+}
+
+impl UserSession<'_> {
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    fn check(&self, path: &str, access: Access) -> Result<(), FsError> {
+        if self.fs.access_as(&self.user, path, access)? {
+            Ok(())
+        } else {
+            Err(FsError::PermissionDenied)
+        }
+    }
+
+    fn check_parent(&self, path: &str) -> Result<(), FsError> {
+        self.check(&parent(path).ok_or(FsError::InvalidPath)?, Access::Write)
+    }
+
+    /// Creates a node like [`FileSystem::create`], owned by the session's
+    /// user unless the filesystem inherits owners from parent directories.
+    pub fn create(
+        &mut self,
+        path: &str,
+        content: Option<Vec<u8>>,
+        is_directory: bool,
+    ) -> Result<(), FsError> {
+        self.check_parent(path)?;
+        self.fs.create(path, content, is_directory)?;
+        if !self.fs.inherit_metadata {
+            self.fs.set_owner(path, &self.user)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, FsError> {
+        self.check(path, Access::Read)?;
+        self.fs.read_file(path)
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, FsError> {
+        self.check(path, Access::Read)?;
+        self.fs.read_to_string(path)
+    }
+
+    pub fn write_file(
+        &mut self,
+        path: &str,
+        content: Vec<u8>,
+        append: bool,
+    ) -> Result<(), FsError> {
+        self.check(path, Access::Write)?;
+        self.fs.write_file(path, content, append)
+    }
+
+    pub fn list_directory(&self, path: &str) -> Result<Vec<String>, FsError> {
+        self.check(path, Access::Read)?;
+        self.fs.list_directory(path)
+    }
+
+    pub fn delete(&mut self, path: &str) -> Result<(), FsError> {
+        self.check_parent(path)?;
+        self.fs.delete(path)
+    }
+
+    /// Changes the mode like [`FileSystem::chmod`]. Only the owner may do
+    /// this, whatever the permissions say.
+    pub fn chmod(&mut self, path: &str, mode: &str) -> Result<(), FsError> {
+        let owner = &self.fs.metadata_at(path)?.owner;
+        if *owner != self.user {
+            return Err(FsError::PermissionDenied);
+        }
+        self.fs.chmod(path, mode)
+    }
+}
+
+impl FileSystem {
+    /// Returns a handle whose operations act on behalf of `user`.
+    pub fn as_user(&mut self, user: &str) -> UserSession<'_> {
+        UserSession {
+            fs: self,
+            user: user.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FileSystem, FsError};
+
+    #[test]
+    fn bob_cannot_write_alices_file() {
+        let mut fs = FileSystem::new();
+        fs.chmod("/", "rwxrwxrwx").unwrap();
+        fs.as_user("alice")
+            .create("/notes", Some(b"mine".to_vec()), false)
+            .unwrap();
+        fs.chmod("/notes", "rw-r--r--").unwrap();
+        assert_eq!(fs.metadata("/notes").unwrap().owner, "alice");
+
+        let mut bob = fs.as_user("bob");
+        assert_eq!(bob.read_file("/notes").unwrap(), b"mine");
+        assert_eq!(
+            bob.write_file("/notes", b"!".to_vec(), true),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            bob.chmod("/notes", "rw-rw-rw-"),
+            Err(FsError::PermissionDenied)
+        );
+        fs.as_user("alice")
+            .write_file("/notes", b"!".to_vec(), true)
+            .unwrap();
+        assert_eq!(fs.read_file("/notes").unwrap(), b"mine!");
+    }
+}
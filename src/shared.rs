@@ -0,0 +1,145 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{FileSystem, FsError, MetadataView};
+
+/// A cloneable, thread-safe handle to a [`FileSystem`].
+///
+/// The whole tree sits behind a single `RwLock`, and each method holds it
+/// for exactly one underlying call: queries take the read lock, mutations
+/// take the write lock. `read_file` counts as a mutation because it updates
+/// `accessed_at`. Use [`SharedFileSystem::read`] or
+/// [`SharedFileSystem::write`] to run several operations under one lock.
+#[derive(Clone)]
+pub struct SharedFileSystem(Arc<RwLock<FileSystem>>);
+
+impl Default for SharedFileSystem {
+    fn default() -> SharedFileSystem {
+        SharedFileSystem::new(FileSystem::new())
+    }
+}
+
+impl SharedFileSystem {
+    pub fn new(fs: FileSystem) -> SharedFileSystem {
+        SharedFileSystem(Arc::new(RwLock::new(fs)))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, FileSystem> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, FileSystem> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn create(
+        &self,
+        path: &str,
+        content: Option<Vec<u8>>,
+        is_directory: bool,
+    ) -> Result<(), FsError> {
+        self.write().create(path, content, is_directory)
+    }
+
+    pub fn create_dir_all(&self, path: &str) -> Result<(), FsError> {
+        self.write().create_dir_all(path)
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, FsError> {
+        self.write().read_file(path)
+    }
+
+    pub fn read_range(&self, path: &str, offset: usize, len: usize) -> Result<Vec<u8>, FsError> {
+        self.read().read_range(path, offset, len)
+    }
+
+    pub fn write_file(&self, path: &str, content: Vec<u8>, append: bool) -> Result<(), FsError> {
+        self.write().write_file(path, content, append)
+    }
+
+    pub fn update_file(&self, path: &str, content: Vec<u8>, append: bool) -> Result<(), FsError> {
+        self.write().update_file(path, content, append)
+    }
+
+    pub fn delete(&self, path: &str) -> Result<(), FsError> {
+        self.write().delete(path)
+    }
+
+    pub fn delete_recursive(&self, path: &str) -> Result<(), FsError> {
+        self.write().delete_recursive(path)
+    }
+
+    pub fn rename(&self, old_path: &str, new_name: &str) -> Result<(), FsError> {
+        self.write().rename(old_path, new_name)
+    }
+
+    pub fn move_node(&self, source_path: &str, target_path: &str) -> Result<(), FsError> {
+        self.write().move_node(source_path, target_path)
+    }
+
+    pub fn copy(
+        &self,
+        source_path: &str,
+        target_path: &str,
+        overwrite: bool,
+    ) -> Result<(), FsError> {
+        self.write().copy(source_path, target_path, overwrite)
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.read().exists(path)
+    }
+
+    pub fn is_file(&self, path: &str) -> bool {
+        self.read().is_file(path)
+    }
+
+    pub fn is_directory(&self, path: &str) -> bool {
+        self.read().is_directory(path)
+    }
+
+    pub fn list_directory(&self, path: &str) -> Result<Vec<String>, FsError> {
+        self.read().list_directory(path)
+    }
+
+    pub fn walk(&self, start: &str) -> Result<Vec<(String, bool)>, FsError> {
+        self.read().walk(start)
+    }
+
+    pub fn metadata(&self, path: &str) -> Result<MetadataView, FsError> {
+        self.read().metadata(path)
+    }
+}
+
+impl From<FileSystem> for SharedFileSystem {
+    fn from(fs: FileSystem) -> SharedFileSystem {
+        SharedFileSystem::new(fs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::SharedFileSystem;
+
+    #[test]
+    fn creates_from_many_threads() {
+        let shared = SharedFileSystem::default();
+        shared.create_dir_all("/out").unwrap();
+        let handles = (0..8)
+            .map(|i| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    shared
+                        .create(&format!("/out/{}.txt", i), Some(vec![i]), false)
+                        .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(shared.list_directory("/out").unwrap().len(), 8);
+        assert_eq!(shared.read_file("/out/5.txt").unwrap(), [5]);
+    }
+}
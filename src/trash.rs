@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use crate::{check_room, normalize, FileSystem, FsError, FsEventKind};
+
+impl FileSystem {
+    /// Moves the node at `path` into the trash instead of deleting it. The
+    /// trash lives outside the tree, so trashed nodes show up in no listing
+    /// or search and don't count toward `used_bytes`. A symbolic link is
+    /// trashed itself, not its target.
+    pub fn trash(&mut self, path: &str) -> Result<(), FsError> {
+        let mut parts = self.resolve(&normalize(path)?, false)?;
+        let name = parts.pop().ok_or(FsError::InvalidPath)?;
+        self.check_unmounted(path)?;
+        let parent_dir = self.navigate_to_directory(&parts)?;
+        match parent_dir.nodes.get(&name) {
+            None => return Err(FsError::NotFound),
+            Some(node) if node.metadata_ref().is_read_only => return Err(FsError::ReadOnly),
+            Some(_) => {}
+        }
+        let node = parent_dir.nodes.remove(&name).unwrap();
+        parts.push(name);
+        let original = parts.iter().map(|p| format!("/{}", p)).collect::<String>();
+        self.notify(FsEventKind::Delete, &original);
+        self.trash.push((original, node));
+        Ok(())
+    }
+
+    /// Puts the node most recently trashed from `original_path` back where
+    /// it was. The parent directory must still exist and the name must be
+    /// free.
+    pub fn restore_from_trash(&mut self, original_path: &str) -> Result<(), FsError> {
+        let mut parts = normalize(original_path)?;
+        let original = parts.iter().map(|p| format!("/{}", p)).collect::<String>();
+        let index = self
+            .trash
+            .iter()
+            .rposition(|(path, _)| {
+                *path == original
+                    || self.case_insensitive && path.to_lowercase() == original.to_lowercase()
+            })
+            .ok_or(FsError::NotFound)?;
+        let name = parts.pop().ok_or(FsError::InvalidPath)?;
+        let parent = self.resolve(&parts, true)?;
+        if self
+            .navigate_physical(&parent)?
+            .contains(&name, self.case_insensitive)
+        {
+            return Err(FsError::AlreadyExists);
+        }
+        let node = &self.trash[index].1;
+        self.check_limits(&parent, node.height(), true, node.node_count())?;
+        let room = self.room_in(&parent, 0);
+        check_room(room, 0, node.content_bytes(&mut HashSet::new()))?;
+
+        let (_, node) = self.trash.remove(index);
+        self.navigate_to_directory(&parent)?
+            .nodes
+            .insert(node.name().to_string(), node);
+        self.notify(FsEventKind::Create, &original);
+        Ok(())
+    }
+
+    /// The original paths of everything in the trash, oldest first. A path
+    /// appears once per node trashed from it.
+    pub fn trashed(&self) -> Vec<String> {
+        self.trash.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    pub fn empty_trash(&mut self) -> Result<(), FsError> {
+        self.check_thawed()?;
+        self.trash.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FileSystem, FsError};
+
+    #[test]
+    fn trash_then_restore() {
+        let mut fs = FileSystem::new();
+        fs.create_dir_all("/docs").unwrap();
+        fs.create("/docs/a.txt", Some(b"a".to_vec()), false)
+            .unwrap();
+        fs.trash("/docs/a.txt").unwrap();
+        assert!(fs.list_directory("/docs").unwrap().is_empty());
+        assert_eq!(fs.trashed(), ["/docs/a.txt"]);
+
+        fs.restore_from_trash("/docs/a.txt").unwrap();
+        assert_eq!(fs.read_file("/docs/a.txt").unwrap(), b"a");
+        assert!(fs.trashed().is_empty());
+        assert_eq!(fs.restore_from_trash("/docs/a.txt"), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn failed_transaction_rolls_back_trash() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        let result = fs.transaction(|fs| {
+            fs.trash("/a")?;
+            Err(FsError::Cancelled)
+        });
+        assert_eq!(result, Err(FsError::Cancelled));
+        assert!(fs.trashed().is_empty());
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+    }
+
+    #[test]
+    fn clone_does_not_share_trashed_content() {
+        let mut fs = FileSystem::new();
+        fs.create("/a", Some(b"a".to_vec()), false).unwrap();
+        fs.trash("/a").unwrap();
+        let mut copy = fs.clone();
+        copy.restore_from_trash("/a").unwrap();
+        copy.write_file("/a", b"b".to_vec(), true).unwrap();
+        fs.restore_from_trash("/a").unwrap();
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+    }
+}
@@ -0,0 +1,160 @@
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use crate::{join, normalize, FileSystem, FsError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsEventKind {
+    Create,
+    Modify,
+    Delete,
+    /// Permissions, ownership, flags, tags or other attributes changed.
+    Metadata,
+    /// The node moved from the event's `path` to `to`.
+    Rename {
+        to: String,
+    },
+}
+
+/// A change reported to the callbacks registered with
+/// [`FileSystem::watch`]. Paths are absolute, with symbolic links in the
+/// parent directories resolved. A `Modify` through a symbolic link is
+/// reported at the file the link leads to, whose content changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsEvent {
+    pub kind: FsEventKind,
+    pub path: String,
+}
+
+/// Keeps a watcher registered. Dropping it unsubscribes the callback.
+#[derive(Debug)]
+#[must_use = "dropping the handle unsubscribes the watcher"]
+pub struct WatchHandle {
+    _token: Arc<()>,
+}
+
+pub(crate) struct Watcher {
+    path: String,
+    token: Weak<()>,
+    /// Behind a mutex only so that `FileSystem` stays `Sync`; it is always
+    /// reached through `&mut self`.
+    callback: Mutex<Box<dyn FnMut(FsEvent) + Send>>,
+}
+
+impl Watcher {
+    fn covers(&self, path: &str) -> bool {
+        self.path == "/"
+            || path
+                .strip_prefix(self.path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+impl FileSystem {
+    /// Calls `callback` for every change to the node at `path` or anything
+    /// beneath it, until the returned handle is dropped. A rename is
+    /// reported when either end lies beneath `path`. `path` need not exist
+    /// yet. Watchers are not carried over by `clone`.
+    pub fn watch<F>(&mut self, path: &str, callback: F) -> WatchHandle
+    where
+        F: FnMut(FsEvent) + Send + 'static,
+    {
+        let path = self.event_path(path).unwrap_or_else(|_| join("/", path));
+        let token = Arc::new(());
+        self.watchers.push(Watcher {
+            path,
+            token: Arc::downgrade(&token),
+            callback: Mutex::new(Box::new(callback)),
+        });
+        WatchHandle { _token: token }
+    }
+
+    /// The absolute path of `path`, following links in all but the last
+    /// component.
+    fn event_path(&self, path: &str) -> Result<String, FsError> {
+        let parts = self.resolve(&normalize(path)?, false)?;
+        Ok(format!("/{}", parts.join("/")))
+    }
+
+    /// Reports an event at `path` to the tag index, the audit log and the
+    /// live watchers covering it, after copying changed file metadata to
+    /// the file's other hard links. The parent directories of `path` must
+    /// still exist.
+    pub(crate) fn notify(&mut self, kind: FsEventKind, path: &str) {
+        let follow_last = kind == FsEventKind::Modify;
+        let Ok(parts) = normalize(path).and_then(|parts| self.resolve(&parts, follow_last)) else {
+            return;
+        };
+        let path = format!("/{}", parts.join("/"));
+        let kind = match kind {
+            FsEventKind::Rename { to } => FsEventKind::Rename {
+                to: self.event_path(&to).unwrap_or(to),
+            },
+            kind => kind,
+        };
+        if matches!(kind, FsEventKind::Modify | FsEventKind::Metadata) {
+            self.sync_links(&path);
+        }
+        self.update_indexes(&kind, &parts);
+        self.watchers
+            .retain(|watcher| watcher.token.strong_count() > 0);
+        if self.watchers.is_empty() && self.audit_log.is_none() {
+            return;
+        }
+        self.record(&kind, &path);
+        for watcher in &mut self.watchers {
+            let renamed_in = matches!(&kind, FsEventKind::Rename { to } if watcher.covers(to));
+            if watcher.covers(&path) || renamed_in {
+                let callback = watcher
+                    .callback
+                    .get_mut()
+                    .unwrap_or_else(PoisonError::into_inner);
+                callback(FsEvent {
+                    kind: kind.clone(),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{FileSystem, FsEvent, FsEventKind, WatchHandle};
+
+    fn recorder(fs: &mut FileSystem, path: &str) -> (WatchHandle, Arc<Mutex<Vec<FsEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let handle = fs.watch(path, move |event| sink.lock().unwrap().push(event));
+        (handle, events)
+    }
+
+    #[test]
+    fn watcher_sees_create_and_delete() {
+        let mut fs = FileSystem::new();
+        fs.create("/dir", None, true).unwrap();
+        fs.create("/other", None, false).unwrap();
+        let (handle, events) = recorder(&mut fs, "/dir");
+        fs.create("/dir/a", None, false).unwrap();
+        fs.delete("/other").unwrap();
+        fs.delete("/dir/a").unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                FsEvent {
+                    kind: FsEventKind::Create,
+                    path: "/dir/a".to_string(),
+                },
+                FsEvent {
+                    kind: FsEventKind::Delete,
+                    path: "/dir/a".to_string(),
+                },
+            ]
+        );
+
+        drop(handle);
+        fs.create("/dir/b", None, false).unwrap();
+        assert_eq!(events.lock().unwrap().len(), 2);
+    }
+}